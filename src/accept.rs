@@ -0,0 +1,80 @@
+// src/accept.rs
+
+// the quality value an Accept header assigns to a media type; the most specific matching range
+// wins (`type/subtype` over `type/*` over `*/*`), and an empty header accepts everything
+pub fn media_quality(accept: &str, media_type: &str) -> f32 {
+    if accept.trim().is_empty() {
+        return 1.0;
+    }
+
+    let (kind, subtype) = media_type.split_once('/').unwrap_or((media_type, ""));
+    let mut best: Option<(u8, f32)> = None;
+
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';');
+        let range = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        let Some((range_kind, range_subtype)) = range.split_once('/') else {
+            continue;
+        };
+        let specificity = if range_kind == "*" && range_subtype == "*" {
+            0
+        } else if range_kind.eq_ignore_ascii_case(kind) && range_subtype == "*" {
+            1
+        } else if range_kind.eq_ignore_ascii_case(kind)
+            && range_subtype.eq_ignore_ascii_case(subtype)
+        {
+            2
+        } else {
+            continue;
+        };
+
+        if best.is_none_or(|(current, _)| specificity > current) {
+            best = Some((specificity, quality));
+        }
+    }
+
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+// whether the client would rather have JSON than HTML; a tie goes to HTML, so browsers sending
+// `*/*` keep getting pages
+pub fn prefers_json(accept: &str) -> bool {
+    let json = media_quality(accept, "application/json");
+    json > 0.0 && json > media_quality(accept, "text/html")
+}
+
+// whether the client accepts HTML at least as much as JSON
+pub fn prefers_html(accept: &str) -> bool {
+    let html = media_quality(accept, "text/html");
+    html > 0.0 && html >= media_quality(accept, "application/json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_between_html_and_json() {
+        let browser = "text/html,application/json;q=0.9";
+        assert!(prefers_html(browser));
+        assert!(!prefers_json(browser));
+
+        assert!(prefers_html("*/*"));
+        assert!(!prefers_json("*/*"));
+
+        assert!(prefers_json("application/json"));
+        assert!(!prefers_html("application/json"));
+
+        // a type wildcard counts, and the specific range overrides it
+        assert!(prefers_json("application/*, text/html;q=0.5"));
+        assert!(prefers_html(
+            "application/*;q=0.8, application/json;q=0.1, */*;q=0.5"
+        ));
+        assert_eq!(media_quality("text/*;q=0.3, */*", "text/html"), 0.3);
+    }
+}
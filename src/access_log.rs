@@ -0,0 +1,22 @@
+// src/access_log.rs
+
+// dependencies
+use std::path::PathBuf;
+
+// type alias for a user supplied access log sink, called once per request
+pub type AccessLogSink = Box<dyn Fn(&AccessLogRecord) + Send + Sync>;
+
+// struct type which represents one entry in the access log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessLogRecord {
+    // the request path as received
+    pub request_path: String,
+    // the file on disk the request resolved to, if any
+    pub resolved_path: Option<PathBuf>,
+    // the HTTP status the outcome maps to
+    pub status: u16,
+    // the number of body bytes served
+    pub bytes: u64,
+    // the MIME type of the served body, if any
+    pub mime_type: Option<String>,
+}
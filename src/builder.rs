@@ -0,0 +1,653 @@
+// src/builder.rs
+
+// dependencies
+use crate::access_log::{AccessLogRecord, AccessLogSink};
+use crate::cache::CacheConfig;
+use crate::config::{
+    BareDirectoryPolicy, DEFAULT_MAX_PATH_LEN, IndexSelection, MountRootPolicy,
+    ServiceWorkerConfig, StaticServerConfig,
+};
+use crate::encoding::Encoding;
+use crate::errors::ConfigError;
+use crate::hash::DigestAlgo;
+use crate::pre_serve::{PreServeDecision, PreServeHook, RequestContext};
+use crate::static_server::StaticServer;
+use crate::transform::{HtmlMetaInjector, TransformHook};
+#[cfg(feature = "tokio")]
+use crate::vfs::AsyncVfs;
+use crate::vfs::Vfs;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+// struct type which builds a static file server one option at a time
+pub struct StaticServerBuilder {
+    config: StaticServerConfig,
+    access_log: Option<AccessLogSink>,
+    pre_serve: Option<PreServeHook>,
+    transform: Option<TransformHook>,
+    html_meta_injector: Option<HtmlMetaInjector>,
+    vfs: Option<Arc<dyn Vfs>>,
+    #[cfg(feature = "tokio")]
+    async_vfs: Option<Arc<dyn AsyncVfs>>,
+}
+
+// methods for the StaticServerBuilder type
+impl StaticServerBuilder {
+    // start a builder serving files from the given root directory
+    pub fn new(root_dir: impl Into<PathBuf>) -> Self {
+        StaticServerBuilder {
+            config: StaticServerConfig {
+                root_dir: root_dir.into(),
+                ..Default::default()
+            },
+            access_log: None,
+            pre_serve: None,
+            transform: None,
+            html_meta_injector: None,
+            vfs: None,
+            #[cfg(feature = "tokio")]
+            async_vfs: None,
+        }
+    }
+
+    // set the path prefix the server is mounted under
+    pub fn mount_path(mut self, mount_path: impl Into<Cow<'static, str>>) -> Self {
+        self.config.mount_path = mount_path.into();
+        self
+    }
+
+    // set whether an index file is served for directory requests
+    pub fn serve_index(mut self, serve_index: bool) -> Self {
+        self.config.serve_index = serve_index;
+        self
+    }
+
+    // set the maximum accepted request path length, or None for no limit
+    pub fn max_path_len(mut self, max_path_len: Option<usize>) -> Self {
+        self.config.max_path_len = max_path_len;
+        self
+    }
+
+    // set whether dotfiles and dot-directories are served
+    pub fn serve_hidden(mut self, serve_hidden: bool) -> Self {
+        self.config.serve_hidden = serve_hidden;
+        self
+    }
+
+    // set whether `X-Content-Type-Options: nosniff` is attached to served files
+    pub fn nosniff(mut self, nosniff: bool) -> Self {
+        self.config.nosniff = nosniff;
+        self
+    }
+
+    // set whether request paths may traverse symlinks inside the root directory
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.config.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    // set whether symlinks must stay inside the tenant directory named by the first segment
+    pub fn per_tenant_jail(mut self, per_tenant_jail: bool) -> Self {
+        self.config.per_tenant_jail = per_tenant_jail;
+        self
+    }
+
+    // set whether files are opened with O_NOFOLLOW (unix only)
+    pub fn open_nofollow(mut self, open_nofollow: bool) -> Self {
+        self.config.open_nofollow = open_nofollow;
+        self
+    }
+
+    // set whether served HTML indexes get a <base href> for the mount path
+    pub fn inject_base_href(mut self, inject_base_href: bool) -> Self {
+        self.config.inject_base_href = inject_base_href;
+        self
+    }
+
+    // set the file names tried, in order, as a directory's index
+    pub fn index_files(mut self, index_files: Vec<String>) -> Self {
+        self.config.index_files = index_files;
+        self
+    }
+
+    // set how the index is chosen when several index files exist
+    pub fn index_selection(mut self, index_selection: IndexSelection) -> Self {
+        self.config.index_selection = index_selection;
+        self
+    }
+
+    // set the most index files tried per directory request
+    pub fn max_index_candidates(mut self, max: usize) -> Self {
+        self.config.max_index_candidates = max;
+        self
+    }
+
+    // set the answer to directory requests which aren't served an index
+    pub fn bare_directory(mut self, policy: BareDirectoryPolicy) -> Self {
+        self.config.bare_directory = policy;
+        self
+    }
+
+    // set the answer to a request for exactly the mount path
+    pub fn mount_root(mut self, policy: MountRootPolicy) -> Self {
+        self.config.mount_root = policy;
+        self
+    }
+
+    // set the mount-relative directory prefixes under which index files are never served
+    pub fn no_index_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.config.no_index_prefixes = prefixes;
+        self
+    }
+
+    // set the mount-relative prefixes whose files are served with a fixed MIME type
+    pub fn force_mime_for_prefix(mut self, forced: HashMap<String, String>) -> Self {
+        self.config.force_mime_for_prefix = forced;
+        self
+    }
+
+    // set the MIME types served for file extensions, consulted before guessing
+    pub fn mime_overrides(mut self, overrides: HashMap<String, String>) -> Self {
+        self.config.mime_overrides = overrides;
+        self
+    }
+
+    // serve only files with these extensions
+    pub fn allowed_extensions(mut self, extensions: HashSet<String>) -> Self {
+        self.config.allowed_extensions = Some(extensions);
+        self
+    }
+
+    // never serve files with these extensions
+    pub fn denied_extensions(mut self, extensions: HashSet<String>) -> Self {
+        self.config.denied_extensions = extensions;
+        self
+    }
+
+    // set the (magic prefix, MIME type) pairs used to type files the path can't
+    pub fn sniff_table(mut self, table: Vec<(Vec<u8>, String)>) -> Self {
+        self.config.sniff_table = table;
+        self
+    }
+
+    // set the hosts allowed to embed files, for hotlink protection
+    pub fn allowed_referers(mut self, hosts: Vec<String>) -> Self {
+        self.config.allowed_referers = Some(hosts);
+        self
+    }
+
+    // set whether requests without a Referer pass the hotlink check
+    pub fn allow_missing_referer(mut self, allow: bool) -> Self {
+        self.config.allow_missing_referer = allow;
+        self
+    }
+
+    // set the host requests on other hosts are redirected to
+    pub fn canonical_host(mut self, host: impl Into<String>) -> Self {
+        self.config.canonical_host = Some(host.into());
+        self
+    }
+
+    // set the largest file the buffering reads will load
+    pub fn max_file_size(mut self, limit: u64) -> Self {
+        self.config.max_file_size = Some(limit);
+        self
+    }
+
+    // set whether a lone `.gz` file is served for requests naming the identity file
+    pub fn serve_lone_gzip(mut self, serve_lone_gzip: bool) -> Self {
+        self.config.serve_lone_gzip = serve_lone_gzip;
+        self
+    }
+
+    // set the largest body decompressed from a lone `.gz` file
+    pub fn max_decompressed_size(mut self, limit: u64) -> Self {
+        self.config.max_decompressed_size = Some(limit);
+        self
+    }
+
+    // set whether text files announce the charset of their byte order mark
+    pub fn detect_charset(mut self, detect_charset: bool) -> Self {
+        self.config.detect_charset = detect_charset;
+        self
+    }
+
+    // set the charset appended to text MIME types, e.g. "utf-8"
+    pub fn default_charset(mut self, charset: impl Into<String>) -> Self {
+        self.config.default_charset = Some(charset.into());
+        self
+    }
+
+    // set the nginx-style try_files chain, e.g. ["$uri", "$uri/", "/index.html"]
+    pub fn try_files(mut self, try_files: Vec<String>) -> Self {
+        self.config.try_files = try_files;
+        self
+    }
+
+    // set whether extension-less paths fall back to a file with the clean URL extension
+    pub fn clean_urls(mut self, clean_urls: bool) -> Self {
+        self.config.clean_urls = clean_urls;
+        self
+    }
+
+    // set the extension tried for clean URLs, e.g. "htm"
+    pub fn clean_url_extension(mut self, extension: impl Into<String>) -> Self {
+        self.config.clean_url_extension = extension.into();
+        self
+    }
+
+    // set whether missed extension-less paths fall back to the root index file
+    pub fn spa_fallback(mut self, spa_fallback: bool) -> Self {
+        self.config.spa_fallback = spa_fallback;
+        self
+    }
+
+    // set the origins announced with preconnect Link headers on HTML responses
+    pub fn preconnect_origins(mut self, origins: Vec<String>) -> Self {
+        self.config.preconnect_origins = origins;
+        self
+    }
+
+    // set whether directories without an index are answered with an HTML listing
+    pub fn directory_listing(mut self, directory_listing: bool) -> Self {
+        self.config.directory_listing = directory_listing;
+        self
+    }
+
+    // set the page, relative to the root directory, served for forbidden outcomes
+    pub fn forbidden_page(mut self, page: impl Into<PathBuf>) -> Self {
+        self.config.forbidden_page = Some(page.into());
+        self
+    }
+
+    // set the page, relative to the root directory, served when nothing is found
+    pub fn not_found_file(mut self, page: impl Into<PathBuf>) -> Self {
+        self.config.not_found_file = Some(page.into());
+        self
+    }
+
+    // set the pages, relative to the root directory, served per error status
+    pub fn error_pages(mut self, pages: HashMap<u16, PathBuf>) -> Self {
+        self.config.error_pages = pages;
+        self
+    }
+
+    // set the build identifier used to derive ETags
+    pub fn build_id(mut self, build_id: impl Into<String>) -> Self {
+        self.config.build_id = Some(build_id.into());
+        self
+    }
+
+    // set the modification time reported for every file
+    pub fn fixed_last_modified(mut self, modified: SystemTime) -> Self {
+        self.config.fixed_last_modified = Some(modified);
+        self
+    }
+
+    // set whether index ETags also cover the content of the files beside the index
+    pub fn combined_index_etag(mut self, combined_index_etag: bool) -> Self {
+        self.config.combined_index_etag = combined_index_etag;
+        self
+    }
+
+    // set how long symlink resolutions are cached for
+    pub fn symlink_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.config.symlink_cache_ttl = Some(ttl);
+        self
+    }
+
+    // set the most symlink resolutions the cache remembers
+    pub fn symlink_cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.symlink_cache_capacity = capacity;
+        self
+    }
+
+    // set how long missing request paths are remembered
+    pub fn negative_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.config.negative_cache_ttl = Some(ttl);
+        self
+    }
+
+    // set the most missing paths the negative cache remembers
+    pub fn negative_cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.negative_cache_capacity = capacity;
+        self
+    }
+
+    // set the directory which answers ACME HTTP-01 challenges
+    pub fn acme_challenge_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.acme_challenge_dir = Some(dir.into());
+        self
+    }
+
+    // set whether served indexes carry a Content-Location header naming the index file
+    pub fn content_location(mut self, content_location: bool) -> Self {
+        self.config.content_location = content_location;
+        self
+    }
+
+    // set the Cache-Control directive attached to served files
+    pub fn cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.config.cache_control = Some(cache_control.into());
+        self
+    }
+
+    // set whether content-hashed file names are cached as immutable for a year
+    pub fn immutable_for_hashed(mut self, immutable: bool) -> Self {
+        self.config.immutable_for_hashed = immutable;
+        self
+    }
+
+    // set the stale-while-revalidate window appended to the Cache-Control directive
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.config.stale_while_revalidate = Some(window);
+        self
+    }
+
+    // set the stale-if-error window appended to the Cache-Control directive
+    pub fn stale_if_error(mut self, window: Duration) -> Self {
+        self.config.stale_if_error = Some(window);
+        self
+    }
+
+    // set the Alt-Svc header value attached to served files
+    pub fn alt_svc(mut self, alt_svc: impl Into<String>) -> Self {
+        self.config.alt_svc = Some(alt_svc.into());
+        self
+    }
+
+    // set the Cross-Origin-Resource-Policy header value attached to served files
+    pub fn cross_origin_resource_policy(mut self, policy: impl Into<String>) -> Self {
+        self.config.cross_origin_resource_policy = Some(policy.into());
+        self
+    }
+
+    // set the Cross-Origin-Embedder-Policy header value attached to served files
+    pub fn cross_origin_embedder_policy(mut self, policy: impl Into<String>) -> Self {
+        self.config.cross_origin_embedder_policy = Some(policy.into());
+        self
+    }
+
+    // set the Timing-Allow-Origin header value attached to served files
+    pub fn timing_allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.config.timing_allow_origin = Some(origin.into());
+        self
+    }
+
+    // set the service worker script and the scope it is allowed to control
+    pub fn service_worker(mut self, path: impl Into<String>, scope: impl Into<String>) -> Self {
+        self.config.service_worker = Some(ServiceWorkerConfig {
+            path: path.into(),
+            scope: scope.into(),
+        });
+        self
+    }
+
+    // set how long read_file_async waits for a body read
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.read_timeout = Some(timeout);
+        self
+    }
+
+    // set the algorithm of the Content-Digest header attached to served bodies
+    pub fn content_digest(mut self, algorithm: DigestAlgo) -> Self {
+        self.config.content_digest = Some(algorithm);
+        self
+    }
+
+    // set whether bodies are gzip-compressed at read time for clients which accept it
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.config.compress = compress;
+        self
+    }
+
+    // set the smallest file compressed at read time
+    pub fn compress_min_size(mut self, min_size: usize) -> Self {
+        self.config.compress_min_size = min_size;
+        self
+    }
+
+    // set the extension of precompressed siblings in a coding, e.g. `gzip` for `app.js.gzip`
+    pub fn precompressed_extension(mut self, encoding: Encoding, extension: &str) -> Self {
+        self.config
+            .precompressed_extensions
+            .insert(encoding, extension.to_string());
+        self
+    }
+
+    // set how many streams and opened files may be held at the same time
+    pub fn max_open_streams(mut self, limit: usize) -> Self {
+        self.config.max_open_streams = Some(limit);
+        self
+    }
+
+    // enable the in-memory LRU cache of served bodies
+    pub fn cache(mut self, cache: CacheConfig) -> Self {
+        self.config.cache = Some(cache);
+        self
+    }
+
+    // set the filesystem the server reads from
+    pub fn vfs(mut self, vfs: Arc<dyn Vfs>) -> Self {
+        self.vfs = Some(vfs);
+        self
+    }
+
+    // set the filesystem read_file_async reads bodies from
+    #[cfg(feature = "tokio")]
+    pub fn async_vfs(mut self, vfs: Arc<dyn AsyncVfs>) -> Self {
+        self.async_vfs = Some(vfs);
+        self
+    }
+
+    // set a sink which receives an access log record after every request
+    pub fn access_log(mut self, sink: impl Fn(&AccessLogRecord) + Send + Sync + 'static) -> Self {
+        self.access_log = Some(Box::new(sink));
+        self
+    }
+
+    // set a hook which can allow, deny or replace each request after resolution
+    pub fn pre_serve(
+        mut self,
+        hook: impl Fn(&Path, &RequestContext) -> PreServeDecision + Send + Sync + 'static,
+    ) -> Self {
+        self.pre_serve = Some(Box::new(hook));
+        self
+    }
+
+    // set a transform applied to every served body, e.g. a minifier
+    pub fn transform(
+        mut self,
+        transform: impl Fn(&Path, &str, Vec<u8>) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Self {
+        self.transform = Some(Box::new(transform));
+        self
+    }
+
+    // set a source of `<meta property>` tags for HTML bodies, e.g. OpenGraph link previews
+    pub fn html_meta_injector(
+        mut self,
+        injector: impl Fn(&Path) -> Vec<(String, String)> + Send + Sync + 'static,
+    ) -> Self {
+        self.html_meta_injector = Some(Box::new(injector));
+        self
+    }
+
+    // preset for security-conscious deployments; this toggles exactly:
+    //   - serve_hidden = false (dotfiles and dot-directories return Forbidden)
+    //   - nosniff = true (X-Content-Type-Options: nosniff on every served file)
+    //   - follow_symlinks = false (any symlink in the request path returns NotFound)
+    //   - max_path_len = DEFAULT_MAX_PATH_LEN, unless a stricter limit is already set
+    //   - open_nofollow = true (has no effect on non-unix platforms)
+    // options set after this call still override the preset
+    pub fn hardened(mut self) -> Self {
+        self.config.serve_hidden = false;
+        self.config.nosniff = true;
+        self.config.follow_symlinks = false;
+        self.config.max_path_len = Some(
+            self.config
+                .max_path_len
+                .map_or(DEFAULT_MAX_PATH_LEN, |limit| {
+                    limit.min(DEFAULT_MAX_PATH_LEN)
+                }),
+        );
+        self.config.open_nofollow = true;
+        self
+    }
+
+    // finish building the static file server, refusing options which can't be served
+    pub fn build(self) -> Result<StaticServer, ConfigError> {
+        self.config.validate()?;
+        let mut server = StaticServer::from_config(self.config);
+        if let Some(sink) = self.access_log {
+            server.set_access_log(sink);
+        }
+        if let Some(hook) = self.pre_serve {
+            server.set_pre_serve(hook);
+        }
+        if let Some(transform) = self.transform {
+            server.set_transform(transform);
+        }
+        if let Some(injector) = self.html_meta_injector {
+            server.set_html_meta_injector(injector);
+        }
+        if let Some(vfs) = self.vfs {
+            server.set_vfs(vfs);
+        }
+        #[cfg(feature = "tokio")]
+        if let Some(vfs) = self.async_vfs {
+            server.set_async_vfs(vfs);
+        }
+        server.check_root()?;
+        Ok(server)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ServeError;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn builds_with_defaults() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "home").unwrap();
+        fs::write(dir.path().join("app.js"), "ok").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        assert_eq!(server.read_file("/app.js").unwrap().body, b"ok");
+        // index files are off by default
+        assert!(server.read_file("/").is_err());
+    }
+
+    #[test]
+    fn builds_with_every_option_set() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/home.html"), "docs").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/assets")
+            .serve_index(true)
+            .index_files(vec!["home.html".to_string()])
+            .alt_svc(r#"h3=":443""#)
+            .timing_allow_origin("*")
+            .cache_control("public, max-age=60")
+            .compress(true)
+            .hardened()
+            .build()
+            .unwrap();
+        let file = server.read_file("/assets/docs/").unwrap();
+        assert_eq!(file.body, b"docs");
+        assert!(
+            file.headers
+                .contains(&("Timing-Allow-Origin", "*".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_options_which_cannot_be_served() {
+        let dir = tempdir().unwrap();
+        let build = |builder: StaticServerBuilder| builder.build().err();
+
+        assert_eq!(
+            build(StaticServer::builder(dir.path()).mount_path("assets")),
+            Some(ConfigError::InvalidMountPath("assets".to_string()))
+        );
+        assert_eq!(
+            build(StaticServer::builder(dir.path()).mount_path("/a?b")),
+            Some(ConfigError::InvalidMountPath("/a?b".to_string()))
+        );
+        assert_eq!(
+            build(
+                StaticServer::builder(dir.path())
+                    .serve_index(true)
+                    .index_files(Vec::new())
+            ),
+            Some(ConfigError::EmptyIndexFiles)
+        );
+        assert_eq!(
+            build(StaticServer::builder(dir.path()).alt_svc(" ")),
+            Some(ConfigError::InvalidHeaderValue { header: "Alt-Svc" })
+        );
+    }
+
+    #[test]
+    fn hardened_server_blocks_dotfiles_and_traversal() {
+        let parent = tempdir().unwrap();
+        let root = parent.path().join("public");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("app.js"), "ok").unwrap();
+        fs::write(root.join(".env"), "SECRET=1").unwrap();
+        fs::write(parent.path().join("outside.txt"), "nope").unwrap();
+
+        let server = StaticServer::builder(&root)
+            .mount_path("/static")
+            .hardened()
+            .build()
+            .unwrap();
+
+        let file = server.read_file("/static/app.js").unwrap();
+        assert_eq!(file.body, b"ok");
+        assert!(
+            file.headers
+                .contains(&("X-Content-Type-Options", "nosniff".to_string()))
+        );
+
+        assert!(matches!(
+            server.read_file("/static/.env"),
+            Err(ServeError::Forbidden)
+        ));
+        assert!(matches!(
+            server.read_file("/static/../outside.txt"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardened_server_rejects_symlinks() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("real.txt"), "real").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("real.txt"), dir.path().join("link.txt"))
+            .unwrap();
+
+        let relaxed = StaticServer::builder(dir.path()).build().unwrap();
+        assert!(relaxed.read_file("/link.txt").is_ok());
+
+        let hardened = StaticServer::builder(dir.path())
+            .hardened()
+            .build()
+            .unwrap();
+        assert!(hardened.read_file("/real.txt").is_ok());
+        assert!(matches!(
+            hardened.read_file("/link.txt"),
+            Err(ServeError::NotFound)
+        ));
+    }
+}
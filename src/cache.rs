@@ -0,0 +1,329 @@
+// src/cache.rs
+
+// dependencies
+use crate::encoding::Encoding;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// struct type which represents configuration for the in-memory file cache
+#[derive(Clone, Debug, Deserialize)]
+pub struct CacheConfig {
+    // maximum number of cached bodies; every encoding of a file counts as its own entry
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    // maximum combined size of the cached bodies, in bytes
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    // files smaller than this many bytes are always read from disk
+    #[serde(default)]
+    pub cache_min_size: u64,
+    // files larger than this many bytes are always read from disk; None means only the byte
+    // budget limits them
+    #[serde(default)]
+    pub cache_max_file_size: Option<u64>,
+}
+
+// implement the Default trait for the CacheConfig type
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_entries: default_max_entries(),
+            max_bytes: default_max_bytes(),
+            cache_min_size: 0,
+            cache_max_file_size: None,
+        }
+    }
+}
+
+// struct type which represents a snapshot of the cache counters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub identity_hits: u64,
+    pub gzip_hits: u64,
+    pub brotli_hits: u64,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+// enum type which represents where a served body came from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileSource {
+    // read from the filesystem (and cached afterwards, when eligible)
+    #[default]
+    Disk,
+    // served from the in-memory cache
+    Cache,
+}
+
+// struct type which represents a least-recently-used cache of served bodies, keyed by the
+// canonical path, the encoding of the body and whether it was rewritten as a served index
+// (a `<base href>` is only injected when a file is reached as a directory's index, so the
+// same file has two identity bodies)
+pub(crate) struct FileCache {
+    config: CacheConfig,
+    inner: Mutex<CacheInner>,
+}
+
+// struct type which represents the mutable state of the cache
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<CacheKey, CacheEntry>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+// type alias for the key of a cached body: canonical path, encoding and the as-index flag
+type CacheKey = (PathBuf, Encoding, bool);
+
+// struct type which represents one cached body and the file version it was produced from
+struct CacheEntry {
+    body: Vec<u8>,
+    modified: Option<SystemTime>,
+    len: u64,
+    last_used: u64,
+}
+
+// methods for the FileCache type
+impl FileCache {
+    pub fn new(config: CacheConfig) -> Self {
+        FileCache {
+            config,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    // whether a file of the given size is eligible for caching at all
+    pub fn admits(&self, file_len: u64) -> bool {
+        file_len >= self.config.cache_min_size
+            && self
+                .config
+                .cache_max_file_size
+                .is_none_or(|max| file_len <= max)
+    }
+
+    // look up a cached body; when the file on disk no longer matches the cached version,
+    // every encoding of it is dropped and the lookup counts as a miss
+    pub fn get(
+        &self,
+        path: &Path,
+        encoding: Encoding,
+        as_index: bool,
+        metadata: &Metadata,
+    ) -> Option<Vec<u8>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let key = (path.to_path_buf(), encoding, as_index);
+        let fresh = match inner.entries.get_mut(&key) {
+            Some(entry) if entry.matches(metadata) => {
+                entry.last_used = clock;
+                Some(entry.body.clone())
+            }
+            Some(_) => {
+                inner.remove_path(path);
+                None
+            }
+            None => None,
+        };
+
+        match fresh {
+            Some(body) => {
+                inner.stats.hits += 1;
+                match encoding {
+                    Encoding::Identity => inner.stats.identity_hits += 1,
+                    Encoding::Gzip => inner.stats.gzip_hits += 1,
+                    Encoding::Brotli => inner.stats.brotli_hits += 1,
+                }
+                Some(body)
+            }
+            None => {
+                inner.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    // store a body, evicting the least recently used entries until the limits hold again
+    pub fn insert(
+        &self,
+        path: &Path,
+        encoding: Encoding,
+        as_index: bool,
+        metadata: &Metadata,
+        body: &[u8],
+    ) {
+        if body.len() as u64 > self.config.max_bytes || self.config.max_entries == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let entry = CacheEntry {
+            body: body.to_vec(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            last_used: inner.clock,
+        };
+
+        inner.stats.bytes += body.len() as u64;
+        inner.stats.entries += 1;
+        if let Some(replaced) = inner
+            .entries
+            .insert((path.to_path_buf(), encoding, as_index), entry)
+        {
+            inner.stats.bytes -= replaced.body.len() as u64;
+            inner.stats.entries -= 1;
+        }
+
+        while inner.stats.entries > self.config.max_entries
+            || inner.stats.bytes > self.config.max_bytes
+        {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            inner.remove(&oldest);
+        }
+    }
+
+    // drop every cached encoding of a file, returning whether anything was removed
+    pub fn invalidate(&self, path: &Path) -> bool {
+        self.inner.lock().unwrap().remove_path(path)
+    }
+
+    // drop every cached body, keeping the hit/miss counters
+    pub fn invalidate_all(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.stats.entries = 0;
+        inner.stats.bytes = 0;
+    }
+
+    // a snapshot of the hit/miss counters and current occupancy
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats
+    }
+}
+
+// methods for the CacheInner type
+impl CacheInner {
+    fn remove(&mut self, key: &CacheKey) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.stats.bytes -= entry.body.len() as u64;
+                self.stats.entries -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // drop every body cached for a path, in any encoding and either rewrite state
+    fn remove_path(&mut self, path: &Path) -> bool {
+        let mut removed = false;
+        for encoding in [Encoding::Identity, Encoding::Gzip, Encoding::Brotli] {
+            for as_index in [false, true] {
+                removed |= self.remove(&(path.to_path_buf(), encoding, as_index));
+            }
+        }
+        removed
+    }
+}
+
+// methods for the CacheEntry type
+impl CacheEntry {
+    // whether the entry was produced from the file version described by the metadata
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.len == metadata.len() && self.modified == metadata.modified().ok()
+    }
+}
+
+// helper function to supply the default entry limit
+fn default_max_entries() -> usize {
+    1024
+}
+
+// helper function to supply the default byte budget of 64 MiB
+fn default_max_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn evicts_least_recently_used_and_stale_entries() {
+        let dir = tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                fs::write(&path, name).unwrap();
+                path
+            })
+            .collect();
+        let metadata = |path: &Path| fs::metadata(path).unwrap();
+
+        let cache = FileCache::new(CacheConfig {
+            max_entries: 2,
+            ..Default::default()
+        });
+        cache.insert(
+            &paths[0],
+            Encoding::Identity,
+            false,
+            &metadata(&paths[0]),
+            b"a",
+        );
+        cache.insert(
+            &paths[1],
+            Encoding::Identity,
+            false,
+            &metadata(&paths[1]),
+            b"b",
+        );
+        // touch "a" so "b" becomes the eviction candidate
+        assert!(
+            cache
+                .get(&paths[0], Encoding::Identity, false, &metadata(&paths[0]))
+                .is_some()
+        );
+        cache.insert(
+            &paths[2],
+            Encoding::Identity,
+            false,
+            &metadata(&paths[2]),
+            b"c",
+        );
+
+        assert!(
+            cache
+                .get(&paths[1], Encoding::Identity, false, &metadata(&paths[1]))
+                .is_none()
+        );
+        assert_eq!(cache.stats().entries, 2);
+
+        // a changed file invalidates the cached copy
+        fs::write(&paths[0], "a, but longer").unwrap();
+        assert!(
+            cache
+                .get(&paths[0], Encoding::Identity, false, &metadata(&paths[0]))
+                .is_none()
+        );
+        assert_eq!(cache.stats().entries, 1);
+    }
+}
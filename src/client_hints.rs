@@ -0,0 +1,65 @@
+// src/client_hints.rs
+
+// dependencies
+use crate::pre_serve::RequestContext;
+
+// struct type which represents the request hints which steer which representation is served
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientHints {
+    // the Accept-Encoding header, if the client sent one
+    pub accept_encoding: Option<String>,
+    // the client sent `Save-Data: on` and wants as few bytes as possible
+    pub save_data: bool,
+}
+
+// methods for the ClientHints type
+impl ClientHints {
+    // read the hints from a request's headers
+    pub fn from_context(context: &RequestContext) -> Self {
+        ClientHints {
+            accept_encoding: context.header("Accept-Encoding").map(str::to_string),
+            save_data: context.header("Save-Data").is_some_and(is_save_data_on),
+        }
+    }
+
+    // the request context carrying these hints, as handed to the pre-serve hook
+    pub(crate) fn to_context(&self) -> RequestContext {
+        let mut context = RequestContext::default();
+        if let Some(accept_encoding) = &self.accept_encoding {
+            context = context.with_header("Accept-Encoding", accept_encoding.as_str());
+        }
+        if self.save_data {
+            context = context.with_header("Save-Data", "on");
+        }
+        context
+    }
+}
+
+// helper function to check a Save-Data header value; `on` is the only token defined, and
+// parameters after it are ignored
+pub(crate) fn is_save_data_on(value: &str) -> bool {
+    value
+        .split(';')
+        .next()
+        .is_some_and(|token| token.trim().eq_ignore_ascii_case("on"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_round_trip_through_the_request_context() {
+        let context = RequestContext::default()
+            .with_header("accept-encoding", "br, gzip")
+            .with_header("save-data", " ON ");
+        let hints = ClientHints::from_context(&context);
+        assert_eq!(hints.accept_encoding.as_deref(), Some("br, gzip"));
+        assert!(hints.save_data);
+        assert_eq!(ClientHints::from_context(&hints.to_context()), hints);
+
+        let off = RequestContext::default().with_header("Save-Data", "off");
+        assert!(!ClientHints::from_context(&off).save_data);
+        assert!(!ClientHints::from_context(&RequestContext::default()).save_data);
+    }
+}
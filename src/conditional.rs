@@ -0,0 +1,177 @@
+// src/conditional.rs
+
+// dependencies
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// whether an If-None-Match header value matches an entity tag; uses the weak comparison
+// required for If-None-Match, so `W/"x"` and `"x"` match each other, and `*` matches anything
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+// whether a file last modified at `modified` is unchanged since the `since` timestamp; HTTP
+// dates only carry whole seconds, so sub-second precision on the file is ignored
+pub fn not_modified_since(modified: SystemTime, since: SystemTime) -> bool {
+    unix_seconds(modified) <= unix_seconds(since)
+}
+
+// parse an HTTP-date (RFC 7231): the preferred IMF-fixdate `Sun, 06 Nov 1994 08:49:37 GMT`,
+// as well as the obsolete RFC 850 `Sunday, 06-Nov-94 08:49:37 GMT` and asctime
+// `Sun Nov  6 08:49:37 1994` forms recipients must accept
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let (day, month, year, time) = match value.split_once(", ") {
+        Some((_, rest)) if rest.contains('-') => {
+            // RFC 850: two-digit years of 70 and above are 19xx, the rest 20xx
+            let (date, rest) = rest.split_once(' ')?;
+            let time = rest.strip_suffix(" GMT")?;
+            let mut parts = date.split('-');
+            let (day, month, year) = (parts.next()?, parts.next()?, parts.next()?);
+            let year: u64 = year.parse().ok().filter(|_| year.len() == 2)?;
+            let year = if year >= 70 { 1900 + year } else { 2000 + year };
+            (day, month, year, time)
+        }
+        Some((_, rest)) => {
+            let rest = rest.strip_suffix(" GMT")?;
+            let mut parts = rest.split(' ');
+            let (day, month, year, time) =
+                (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+            (day, month, year.parse().ok()?, time)
+        }
+        None => {
+            let mut parts = value.split_whitespace().skip(1);
+            let (month, day, time, year) =
+                (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+            (day, month, year.parse().ok()?, time)
+        }
+    };
+
+    let day: u64 = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let mut clock = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (clock.next()??, clock.next()??, clock.next()??);
+    if clock.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 || year < 1970 {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let secs = days * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+// format a timestamp as an IMF-fixdate for the Last-Modified header; times before the epoch
+// are clamped to it
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let days = secs / 86_400;
+    let (year, month, day) = civil_from_days(days);
+    // 1970-01-01 was a Thursday
+    let weekday = WEEKDAYS[((days + 4) % 7) as usize];
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        weekday,
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
+
+// month and weekday names as they appear in HTTP-dates
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+// helper function to count the days from 1970-01-01 to a proleptic Gregorian date
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// helper function to turn a day count since 1970-01-01 back into (year, month, day)
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// helper function to turn a timestamp into whole seconds since the unix epoch
+fn unix_seconds(time: SystemTime) -> i128 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => i128::from(after.as_secs()),
+        Err(before) => -i128::from(before.duration().as_secs()) - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag_matching_is_weak_and_list_aware() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"x\", \"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abcd\"", "\"abc\""));
+    }
+
+    #[test]
+    fn modification_dates_compare_in_whole_seconds() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_000_500);
+        assert!(not_modified_since(
+            modified,
+            UNIX_EPOCH + Duration::from_secs(1_000)
+        ));
+        assert!(!not_modified_since(
+            modified,
+            UNIX_EPOCH + Duration::from_secs(999)
+        ));
+    }
+
+    #[test]
+    fn parses_all_three_http_date_forms() {
+        let expected = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(expected)
+        );
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(expected));
+        assert_eq!(format_http_date(expected), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
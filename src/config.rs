@@ -1,9 +1,22 @@
 // src/config.rs
 
 // dependencies
+use crate::cache::CacheConfig;
+use crate::encoding::Encoding;
+use crate::errors::ConfigError;
+use crate::hash::DigestAlgo;
 use serde::Deserialize;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+// default limit on the length of an incoming request path, in bytes
+pub const DEFAULT_MAX_PATH_LEN: usize = 4096;
+
+// default limit on the body decompressed from a lone `.gz` file when neither
+// max_decompressed_size nor max_file_size is set, in bytes
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
 
 // struct type which represents configuration for a static file server
 #[derive(Clone, Debug, Deserialize)]
@@ -11,4 +24,547 @@ pub struct StaticServerConfig {
     pub mount_path: Cow<'static, str>,
     pub root_dir: PathBuf,
     pub serve_index: bool,
-}
\ No newline at end of file
+    // request paths longer than this are rejected before any decoding or filesystem access;
+    // None disables the check
+    #[serde(default = "default_max_path_len")]
+    pub max_path_len: Option<usize>,
+    // serve files and directories whose names start with a dot; off by default so a stray
+    // `.env` or `.git/config` under the root isn't published
+    #[serde(default)]
+    pub serve_hidden: bool,
+    // attach `X-Content-Type-Options: nosniff` to served files
+    #[serde(default)]
+    pub nosniff: bool,
+    // allow request paths to traverse symlinks, as long as the target stays inside root_dir;
+    // this includes symlinked directories, whose files and index are served under the
+    // requested (link) path
+    #[serde(default = "default_true")]
+    pub follow_symlinks: bool,
+    // treat the first path segment as a tenant directory and keep followed symlinks inside
+    // it, rather than merely inside root_dir; files directly in root_dir are unaffected
+    #[serde(default)]
+    pub per_tenant_jail: bool,
+    // open files with O_NOFOLLOW so a symlink swapped in after resolution is refused (unix only)
+    #[serde(default)]
+    pub open_nofollow: bool,
+    // when an HTML index is served, insert a <base href> pointing at the mount path
+    #[serde(default)]
+    pub inject_base_href: bool,
+    // file names tried, in order, when a directory request is answered with its index
+    #[serde(default = "default_index_files")]
+    pub index_files: Vec<String>,
+    // how the index is chosen when a directory holds several of the index_files
+    #[serde(default)]
+    pub index_selection: IndexSelection,
+    // most index_files tried per directory request, bounding the stat calls a long list costs;
+    // names past the cap are never looked at, and the first name is always tried
+    #[serde(default = "default_max_index_candidates")]
+    pub max_index_candidates: usize,
+    // the answer to a directory request which isn't served an index
+    #[serde(default)]
+    pub bare_directory: BareDirectoryPolicy,
+    // the answer to a request for exactly the mount path, without a trailing slash
+    #[serde(default)]
+    pub mount_root: MountRootPolicy,
+    // mount-relative directory prefixes (e.g. "downloads") under which index files are never served
+    #[serde(default)]
+    pub no_index_prefixes: Vec<String>,
+    // mount-relative prefixes (e.g. "api/") whose files are always served with the given MIME type
+    #[serde(default)]
+    pub force_mime_for_prefix: HashMap<String, String>,
+    // MIME types keyed by file extension (e.g. "wasm"), consulted before guessing; extensions
+    // match regardless of case
+    #[serde(default)]
+    pub mime_overrides: HashMap<String, String>,
+    // when set, only files with one of these extensions are served (an empty string admits
+    // files without one); everything else is a miss
+    #[serde(default)]
+    pub allowed_extensions: Option<HashSet<String>>,
+    // extensions never served, e.g. "map"; ignored while allowed_extensions is set
+    #[serde(default)]
+    pub denied_extensions: HashSet<String>,
+    // (magic prefix, MIME type) pairs matched against a file's first bytes, only when the
+    // type from the path would be application/octet-stream; the first match wins
+    #[serde(default)]
+    pub sniff_table: Vec<(Vec<u8>, String)>,
+    // hosts allowed to embed files, checked by read_file_with_referer against the host of the
+    // Referer header; `*.example.com` also admits every subdomain. None disables the check
+    #[serde(default)]
+    pub allowed_referers: Option<Vec<String>>,
+    // whether read_file_with_referer serves requests without a Referer (direct visits and
+    // privacy-minded clients) while allowed_referers is set
+    #[serde(default = "default_true")]
+    pub allow_missing_referer: bool,
+    // the host assets should be requested on, e.g. `example.com`; host_redirect names the URL
+    // to send requests for any other host to. A value without a scheme means https
+    #[serde(default)]
+    pub canonical_host: Option<String>,
+    // largest file the buffering reads (read_file and friends) will load; bigger files fail
+    // with TooLarge before anything is read, while streams and open_file aren't limited
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    // serve a lone `.gz` file (one without the identity file beside it) for requests naming
+    // the identity file; the extension and dotfile policies judge it by the identity name
+    #[serde(default)]
+    pub serve_lone_gzip: bool,
+    // largest body produced by decompressing a lone `.gz` file for a client which doesn't
+    // accept gzip, or to rewrite it; decoding stops with TooLarge as soon as the output passes
+    // it, so a gzip bomb never fills memory. None falls back to max_file_size, then to
+    // DEFAULT_MAX_DECOMPRESSED_SIZE
+    #[serde(default)]
+    pub max_decompressed_size: Option<u64>,
+    // add a charset parameter to text MIME types whose file starts with a UTF-8 or UTF-16
+    // byte order mark; costs a read of the first bytes of every text file served
+    #[serde(default)]
+    pub detect_charset: bool,
+    // charset appended as `; charset=...` to text MIME types (text/*, JavaScript, JSON, XML)
+    // which don't carry one, e.g. "utf-8"; a charset detected from a byte order mark wins
+    #[serde(default)]
+    pub default_charset: Option<String>,
+    // nginx-style try_files chain, tried in order with the first hit winning; each entry is a
+    // mount-relative path where `$uri` is replaced by the request path, e.g. `$uri`, `$uri.html`
+    // or `/index.html`; an entry ending in `/` must be a directory and serves its index file;
+    // when empty, plain resolution (with serve_index) is used
+    #[serde(default)]
+    pub try_files: Vec<String>,
+    // serve `guide.html` for `/guide` when the path has no extension and isn't a directory;
+    // only applies when try_files is empty, which can express the same with `$uri.html`
+    #[serde(default)]
+    pub clean_urls: bool,
+    // the extension appended for clean URLs, without the leading dot
+    #[serde(default = "default_clean_url_extension")]
+    pub clean_url_extension: String,
+    // serve the root index file for a missed path without an extension, so client-side
+    // routes of a single-page app load the app; missing assets (`logo.png`) still 404
+    #[serde(default)]
+    pub spa_fallback: bool,
+    // answer a directory request which has no index file to serve with a generated HTML
+    // listing of the entries the server would serve, directories first
+    #[serde(default)]
+    pub directory_listing: bool,
+    // origins (`https://cdn.example.com`) announced to browsers with a
+    // `Link: <origin>; rel=preconnect` header on HTML responses, so connections to them are
+    // set up before the page asks for anything
+    #[serde(default)]
+    pub preconnect_origins: Vec<String>,
+    // page, relative to root_dir, served with a 403 status for forbidden outcomes such as a
+    // blocked dotfile; when unset the plain Forbidden error is returned
+    #[serde(default)]
+    pub forbidden_page: Option<PathBuf>,
+    // page, relative to root_dir, served with a 404 status when a request resolves to
+    // nothing; when unset, or when the page itself is missing, the plain NotFound is returned
+    #[serde(default)]
+    pub not_found_file: Option<PathBuf>,
+    // pages, relative to root_dir, served for error outcomes keyed by HTTP status (e.g. 404);
+    // a mapped 403 or 404 page takes precedence over forbidden_page or not_found_file
+    #[serde(default)]
+    pub error_pages: HashMap<u16, PathBuf>,
+    // identifier of the deployed build; when set, ETags are derived from it and the file's path
+    // so that every replica of the same build agrees on them
+    #[serde(default)]
+    pub build_id: Option<String>,
+    // modification time reported for every file in place of its own, e.g. the build time, so
+    // Last-Modified and metadata ETags agree across checkouts whose mtimes differ
+    #[serde(default)]
+    pub fixed_last_modified: Option<SystemTime>,
+    // derive an index file's ETag from its content plus the content of every file beside it,
+    // so an SPA shell revalidates when a hashed asset it references changes; this reads the
+    // whole directory on every index request (including conditional ones), so keep it to
+    // small asset directories
+    #[serde(default)]
+    pub combined_index_etag: bool,
+    // when set (and symlinks are followed), remember where requested paths canonicalize to for
+    // this long instead of re-walking symlink chains on every request
+    #[serde(default)]
+    pub symlink_cache_ttl: Option<Duration>,
+    // the most symlink resolutions the cache remembers
+    #[serde(default = "default_symlink_cache_capacity")]
+    pub symlink_cache_capacity: usize,
+    // when set, remember request paths that resolved to nothing for this long, so repeated
+    // 404s skip the filesystem; a file created meanwhile stays missing until the entry
+    // expires or invalidate/invalidate_all is called
+    #[serde(default)]
+    pub negative_cache_ttl: Option<Duration>,
+    // the most missing paths the negative cache remembers
+    #[serde(default = "default_negative_cache_capacity")]
+    pub negative_cache_capacity: usize,
+    // directory answering ACME HTTP-01 challenges at /.well-known/acme-challenge/<token>,
+    // independent of the mount path, root_dir and the dotfile policy
+    #[serde(default)]
+    pub acme_challenge_dir: Option<PathBuf>,
+    // attach a Content-Location header naming the index file when a directory request is
+    // answered with its index
+    #[serde(default)]
+    pub content_location: bool,
+    // Cache-Control directive attached to served files, e.g. "public, max-age=3600"
+    #[serde(default)]
+    pub cache_control: Option<String>,
+    // send `public, max-age=31536000, immutable` instead of cache_control for files whose
+    // names carry a content hash (`app.3f9a1c2b.js`, `index-B4x9kQ2z.css`), since any change
+    // to them ships under a new name
+    #[serde(default)]
+    pub immutable_for_hashed: bool,
+    // appended to cache_control as `stale-while-revalidate=<seconds>`; ignored without it
+    #[serde(default)]
+    pub stale_while_revalidate: Option<Duration>,
+    // appended to cache_control as `stale-if-error=<seconds>`; ignored without it
+    #[serde(default)]
+    pub stale_if_error: Option<Duration>,
+    // value of an Alt-Svc header advertising HTTP/3 (or another protocol) on served files,
+    // e.g. `h3=":443"; ma=86400`; a value that isn't a legal header value is ignored
+    #[serde(default)]
+    pub alt_svc: Option<String>,
+    // value of a Timing-Allow-Origin header exposing Resource Timing details to other
+    // origins, e.g. "*" or "https://app.example.com"; a value that isn't a legal header value
+    // is ignored
+    #[serde(default)]
+    pub timing_allow_origin: Option<String>,
+    // value of a Cross-Origin-Resource-Policy header: "same-site", "same-origin" or
+    // "cross-origin"
+    #[serde(default)]
+    pub cross_origin_resource_policy: Option<String>,
+    // value of a Cross-Origin-Embedder-Policy header: "unsafe-none", "require-corp" or
+    // "credentialless"; with a matching COOP on the page this enables cross-origin isolation
+    #[serde(default)]
+    pub cross_origin_embedder_policy: Option<String>,
+    // service worker script served with Service-Worker-Allowed and Cache-Control: no-cache
+    #[serde(default)]
+    pub service_worker: Option<ServiceWorkerConfig>,
+    // how long read_file_async waits for a body read before failing with a timeout; the
+    // synchronous API blocks in the read itself and can't enforce this
+    #[serde(default)]
+    pub read_timeout: Option<Duration>,
+    // attach a Content-Digest header computed over the sent body with this algorithm
+    #[serde(default)]
+    pub content_digest: Option<DigestAlgo>,
+    // compress bodies with gzip at read time for clients which accept it
+    #[serde(default)]
+    pub compress: bool,
+    // files smaller than this many bytes are sent uncompressed, since gzip's framing would
+    // outweigh the savings
+    #[serde(default = "default_compress_min_size")]
+    pub compress_min_size: usize,
+    // file extension (without the leading dot) of the precompressed sibling looked for in each
+    // coding, e.g. `gzip` for `app.js.gzip`; a coding left out has no siblings
+    #[serde(default = "default_precompressed_extensions")]
+    pub precompressed_extensions: HashMap<Encoding, String>,
+    // cap on streams and opened files held by callers at the same time; further requests fail
+    // with TooManyOpenStreams until one is dropped
+    #[serde(default)]
+    pub max_open_streams: Option<usize>,
+    // keep served bodies in an in-memory LRU cache; identity and compressed variants of a file
+    // are cached side by side
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+}
+
+// enum type which represents the answer to a directory request without an index
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BareDirectoryPolicy {
+    // ServeError::NotFound, as if the directory didn't exist
+    #[default]
+    NotFound,
+    // ServeError::Forbidden, admitting the directory exists
+    Forbidden,
+    // ServeError::Redirect (302) to the given URL
+    Redirect(String),
+}
+
+// enum type which represents the answer to a request for exactly the mount path (`/static`);
+// `/static/` always resolves the root directory like any other directory request
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MountRootPolicy {
+    // resolve it as the root directory, serving its index when serve_index is on
+    #[default]
+    ServeIndex,
+    // ServeError::Redirect (301) to the mount path with a trailing slash
+    Redirect,
+    // ServeError::NotFound
+    NotFound,
+}
+
+// struct type which represents a service worker script and the scope it may control
+#[derive(Clone, Debug, Deserialize)]
+pub struct ServiceWorkerConfig {
+    // mount-relative path of the script, e.g. "sw.js"
+    pub path: String,
+    // value of the Service-Worker-Allowed header, e.g. "/"
+    pub scope: String,
+}
+
+// enum type which represents how an index file is picked among several existing candidates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexSelection {
+    // the first existing file in index_files order wins
+    #[default]
+    FirstMatch,
+    // the most recently modified existing file wins; ties go to index_files order
+    NewestModified,
+}
+
+// implement the Default trait for the StaticServerConfig type
+impl Default for StaticServerConfig {
+    fn default() -> Self {
+        StaticServerConfig {
+            mount_path: Cow::Borrowed("/"),
+            root_dir: PathBuf::from("."),
+            serve_index: false,
+            max_path_len: default_max_path_len(),
+            serve_hidden: false,
+            nosniff: false,
+            follow_symlinks: true,
+            per_tenant_jail: false,
+            open_nofollow: false,
+            inject_base_href: false,
+            index_files: default_index_files(),
+            index_selection: IndexSelection::default(),
+            max_index_candidates: default_max_index_candidates(),
+            bare_directory: BareDirectoryPolicy::default(),
+            mount_root: MountRootPolicy::default(),
+            no_index_prefixes: Vec::new(),
+            force_mime_for_prefix: HashMap::new(),
+            mime_overrides: HashMap::new(),
+            allowed_extensions: None,
+            denied_extensions: HashSet::new(),
+            sniff_table: Vec::new(),
+            detect_charset: false,
+            default_charset: None,
+            max_file_size: None,
+            serve_lone_gzip: false,
+            max_decompressed_size: None,
+            allowed_referers: None,
+            allow_missing_referer: true,
+            canonical_host: None,
+            try_files: Vec::new(),
+            clean_urls: false,
+            clean_url_extension: default_clean_url_extension(),
+            spa_fallback: false,
+            directory_listing: false,
+            preconnect_origins: Vec::new(),
+            forbidden_page: None,
+            not_found_file: None,
+            error_pages: HashMap::new(),
+            build_id: None,
+            fixed_last_modified: None,
+            combined_index_etag: false,
+            symlink_cache_ttl: None,
+            symlink_cache_capacity: default_symlink_cache_capacity(),
+            negative_cache_ttl: None,
+            negative_cache_capacity: default_negative_cache_capacity(),
+            acme_challenge_dir: None,
+            content_location: false,
+            cache_control: None,
+            immutable_for_hashed: false,
+            stale_while_revalidate: None,
+            stale_if_error: None,
+            alt_svc: None,
+            timing_allow_origin: None,
+            cross_origin_resource_policy: None,
+            cross_origin_embedder_policy: None,
+            service_worker: None,
+            read_timeout: None,
+            content_digest: None,
+            compress: false,
+            compress_min_size: default_compress_min_size(),
+            precompressed_extensions: default_precompressed_extensions(),
+            max_open_streams: None,
+            cache: None,
+        }
+    }
+}
+
+// methods for the StaticServerConfig type
+impl StaticServerConfig {
+    // check the options which can't be served as given; from_config stays lenient and drops
+    // invalid header values, while the builder reports them
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mount_path = self.mount_path.as_ref();
+        if !mount_path.starts_with('/') || mount_path.contains(['?', '#']) {
+            return Err(ConfigError::InvalidMountPath(mount_path.to_string()));
+        }
+        if self.serve_index && self.index_files.is_empty() {
+            return Err(ConfigError::EmptyIndexFiles);
+        }
+
+        if let Some(origin) = self
+            .preconnect_origins
+            .iter()
+            .find(|origin| !is_valid_origin(origin))
+        {
+            return Err(ConfigError::InvalidPreconnectOrigin(origin.clone()));
+        }
+        if let Some(charset) = self
+            .default_charset
+            .as_ref()
+            .filter(|charset| !is_valid_charset(charset))
+        {
+            return Err(ConfigError::InvalidCharset(charset.clone()));
+        }
+        if let Some(extension) = self
+            .precompressed_extensions
+            .iter()
+            .find(|(encoding, extension)| !is_valid_sibling_extension(**encoding, extension))
+            .map(|(_, extension)| extension)
+        {
+            return Err(ConfigError::InvalidPrecompressedExtension(
+                extension.clone(),
+            ));
+        }
+
+        let headers = [
+            ("Alt-Svc", &self.alt_svc),
+            ("Timing-Allow-Origin", &self.timing_allow_origin),
+        ];
+        for (header, value) in headers {
+            if value
+                .as_deref()
+                .is_some_and(|value| !is_valid_header_value(value))
+            {
+                return Err(ConfigError::InvalidHeaderValue { header });
+            }
+        }
+
+        let policies = [
+            (
+                "Cross-Origin-Resource-Policy",
+                &self.cross_origin_resource_policy,
+                &CORP_VALUES[..],
+            ),
+            (
+                "Cross-Origin-Embedder-Policy",
+                &self.cross_origin_embedder_policy,
+                &COEP_VALUES[..],
+            ),
+        ];
+        for (header, value, allowed) in policies {
+            if value
+                .as_deref()
+                .is_some_and(|value| !allowed.contains(&value))
+            {
+                return Err(ConfigError::InvalidHeaderValue { header });
+            }
+        }
+        Ok(())
+    }
+}
+
+// the tokens a Cross-Origin-Resource-Policy header may carry
+pub(crate) const CORP_VALUES: [&str; 3] = ["same-site", "same-origin", "cross-origin"];
+
+// the tokens a Cross-Origin-Embedder-Policy header may carry
+pub(crate) const COEP_VALUES: [&str; 3] = ["unsafe-none", "require-corp", "credentialless"];
+
+// helper function to check a header value is non-empty and made of visible ASCII, spaces
+// and tabs, so it can't smuggle in a line break or a second header
+pub(crate) fn is_valid_header_value(value: &str) -> bool {
+    !value.trim().is_empty()
+        && value
+            .bytes()
+            .all(|b| b == b' ' || b == b'\t' || b.is_ascii_graphic())
+}
+
+// helper function to check a charset name is a bare token such as `utf-8`, which can't carry
+// a second parameter or break out of the Content-Type header
+pub(crate) fn is_valid_charset(charset: &str) -> bool {
+    !charset.is_empty()
+        && charset
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':' | b'+'))
+}
+
+// helper function to check a precompressed sibling extension names a file beside the original:
+// more than dots once a leading dot is dropped, without path separators, and not for identity
+pub(crate) fn is_valid_sibling_extension(encoding: Encoding, extension: &str) -> bool {
+    let extension = extension.strip_prefix('.').unwrap_or(extension);
+    encoding != Encoding::Identity
+        && !extension.trim_matches('.').is_empty()
+        && !extension.contains(['/', '\\'])
+}
+
+// helper function to check an origin is an absolute `scheme://host[:port]` URL, with no path,
+// query or characters which could break out of a Link header
+pub(crate) fn is_valid_origin(origin: &str) -> bool {
+    let Some((scheme, authority)) = origin.split_once("://") else {
+        return false;
+    };
+    let authority = authority.strip_suffix('/').unwrap_or(authority);
+    // an IPv6 literal keeps its brackets, and its colons aren't a port separator
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(v6) => match v6.split_once(']') {
+            Some((address, "")) => (address, None),
+            Some((address, rest)) => match rest.strip_prefix(':') {
+                Some(port) => (address, Some(port)),
+                None => return false,
+            },
+            None => return false,
+        },
+        None => match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+
+    let scheme_ok = scheme
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'));
+    let host_ok = host
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b':'));
+    let port_ok =
+        port.is_none_or(|port| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()));
+    !scheme.is_empty() && scheme_ok && !host.is_empty() && host_ok && port_ok
+}
+
+// helper function to supply the default path length limit
+fn default_max_path_len() -> Option<usize> {
+    Some(DEFAULT_MAX_PATH_LEN)
+}
+
+// helper function to supply the default minimum size for read-time compression
+fn default_compress_min_size() -> usize {
+    1024
+}
+
+// helper function to supply the default precompressed sibling extensions, `gz` and `br`
+fn default_precompressed_extensions() -> HashMap<Encoding, String> {
+    [Encoding::Gzip, Encoding::Brotli]
+        .into_iter()
+        .filter_map(|encoding| {
+            let suffix = encoding.sibling_suffix()?;
+            Some((encoding, suffix.trim_start_matches('.').to_string()))
+        })
+        .collect()
+}
+
+// helper function to supply the default negative cache capacity
+fn default_negative_cache_capacity() -> usize {
+    1024
+}
+
+// helper function to supply the default symlink cache capacity
+fn default_symlink_cache_capacity() -> usize {
+    1024
+}
+
+// helper function to supply the default clean URL extension
+fn default_clean_url_extension() -> String {
+    "html".to_string()
+}
+
+// helper function to supply the default cap on index candidates
+fn default_max_index_candidates() -> usize {
+    16
+}
+
+// helper function to supply the default index file names
+fn default_index_files() -> Vec<String> {
+    vec!["index.html".to_string()]
+}
+
+// helper function for boolean options which default to on
+fn default_true() -> bool {
+    true
+}
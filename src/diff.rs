@@ -0,0 +1,74 @@
+// src/diff.rs
+
+// dependencies
+use std::collections::BTreeMap;
+
+// struct type which represents the files that differ between two asset roots, as sorted
+// mount-relative paths (e.g. `css/app.css`)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RootDiff {
+    // files only in the new root
+    pub added: Vec<String>,
+    // files only in the old root
+    pub removed: Vec<String>,
+    // files in both roots whose content differs
+    pub modified: Vec<String>,
+}
+
+// methods for the RootDiff type
+impl RootDiff {
+    // whether the two roots hold the same files with the same content
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    // compare two maps of relative path to content hash
+    pub(crate) fn between(
+        old: &BTreeMap<String, Vec<u8>>,
+        new: &BTreeMap<String, Vec<u8>>,
+    ) -> Self {
+        let mut diff = RootDiff::default();
+        for (path, hash) in new {
+            match old.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(old_hash) if old_hash != hash => diff.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|path| !new.contains_key(*path))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_paths_into_added_removed_and_modified() {
+        let old = BTreeMap::from([
+            ("a.css".to_string(), vec![1]),
+            ("b.js".to_string(), vec![2]),
+            ("gone.txt".to_string(), vec![3]),
+        ]);
+        let new = BTreeMap::from([
+            ("a.css".to_string(), vec![1]),
+            ("b.js".to_string(), vec![9]),
+            ("new.txt".to_string(), vec![4]),
+        ]);
+
+        assert_eq!(
+            RootDiff::between(&old, &new),
+            RootDiff {
+                added: vec!["new.txt".to_string()],
+                removed: vec!["gone.txt".to_string()],
+                modified: vec!["b.js".to_string()],
+            }
+        );
+        assert!(RootDiff::between(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+// src/encoding.rs
+
+// dependencies
+use serde::Deserialize;
+
+// enum type which represents a content coding the server can send
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    #[serde(rename = "br")]
+    Brotli,
+}
+
+// methods for the Encoding type
+impl Encoding {
+    // the token used for this coding in Accept-Encoding and Content-Encoding headers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Identity => "identity",
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+        }
+    }
+
+    // parse a content coding token, ignoring ASCII case
+    pub fn from_token(token: &str) -> Option<Encoding> {
+        match token.trim().to_ascii_lowercase().as_str() {
+            "identity" => Some(Encoding::Identity),
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "br" => Some(Encoding::Brotli),
+            _ => None,
+        }
+    }
+
+    // the suffix of a precompressed sibling file in this coding, e.g. `app.js.gz`
+    pub fn sibling_suffix(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some(".gz"),
+            Encoding::Brotli => Some(".br"),
+        }
+    }
+
+    // tie-break order when the client weighs several codings equally; smaller is preferred
+    fn preference(&self) -> u8 {
+        match self {
+            Encoding::Brotli => 0,
+            Encoding::Gzip => 1,
+            Encoding::Identity => 2,
+        }
+    }
+}
+
+// the quality value an Accept-Encoding header assigns to a coding; an explicit entry wins over
+// `*`, and a coding that isn't mentioned at all gets 0 (identity is handled by the caller)
+pub fn encoding_quality(accept_encoding: &str, encoding: Encoding) -> f32 {
+    let mut wildcard = None;
+
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let token = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if token == "*" {
+            wildcard = Some(quality);
+        } else if Encoding::from_token(token) == Some(encoding) {
+            return quality;
+        }
+    }
+
+    wildcard.unwrap_or(0.0)
+}
+
+// pick the coding to send from the codings available for a file; the highest quality wins,
+// brotli is preferred over gzip on a tie, and identity is the fallback. Byte ranges never
+// come through here: write_range_to and read_range always slice the identity bytes, so
+// compressed bytes are never ranged and nothing is compressed on the fly for a range
+pub fn negotiate_encoding(accept_encoding: Option<&str>, available: &[Encoding]) -> Encoding {
+    let Some(accept_encoding) = accept_encoding else {
+        return Encoding::Identity;
+    };
+
+    available
+        .iter()
+        .filter(|encoding| **encoding != Encoding::Identity)
+        .map(|encoding| (*encoding, encoding_quality(accept_encoding, *encoding)))
+        .filter(|(_, quality)| *quality > 0.0)
+        .min_by(|(a, qa), (b, qb)| {
+            qb.total_cmp(qa)
+                .then_with(|| a.preference().cmp(&b.preference()))
+        })
+        .map_or(Encoding::Identity, |(encoding, _)| encoding)
+}
+
+// pick the smallest representation the client accepts, for `Save-Data` requests: each
+// available coding comes with its size when known (a precompressed sibling) or None when it
+// would be compressed at read time, which ranks after every known size; quality values only
+// decide what is acceptable, not the order. Identity is the fallback
+pub fn negotiate_smallest(
+    accept_encoding: Option<&str>,
+    available: &[(Encoding, Option<u64>)],
+) -> Encoding {
+    let Some(accept_encoding) = accept_encoding else {
+        return Encoding::Identity;
+    };
+
+    available
+        .iter()
+        .filter(|(encoding, _)| *encoding != Encoding::Identity)
+        .filter(|(encoding, _)| encoding_quality(accept_encoding, *encoding) > 0.0)
+        .min_by_key(|(encoding, size)| (size.unwrap_or(u64::MAX), encoding.preference()))
+        .map_or(Encoding::Identity, |(encoding, _)| *encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Encoding; 3] = [Encoding::Identity, Encoding::Gzip, Encoding::Brotli];
+
+    #[test]
+    fn prefers_brotli_then_quality() {
+        assert_eq!(negotiate_encoding(Some("gzip, br"), &ALL), Encoding::Brotli);
+        assert_eq!(
+            negotiate_encoding(Some("gzip, br;q=0.5"), &ALL),
+            Encoding::Gzip
+        );
+        assert_eq!(
+            negotiate_encoding(Some("*"), &[Encoding::Identity, Encoding::Gzip]),
+            Encoding::Gzip
+        );
+        assert_eq!(
+            negotiate_encoding(Some("gzip;q=0, deflate"), &ALL),
+            Encoding::Identity
+        );
+        assert_eq!(negotiate_encoding(None, &ALL), Encoding::Identity);
+    }
+
+    #[test]
+    fn smallest_acceptable_encoding_wins() {
+        let available = [
+            (Encoding::Identity, Some(1000)),
+            (Encoding::Gzip, Some(300)),
+            (Encoding::Brotli, Some(250)),
+        ];
+        assert_eq!(
+            negotiate_smallest(Some("gzip, br;q=0.5"), &available),
+            Encoding::Brotli
+        );
+        assert_eq!(
+            negotiate_smallest(Some("gzip, br;q=0"), &available),
+            Encoding::Gzip
+        );
+        // a known size beats compressing at read time
+        assert_eq!(
+            negotiate_smallest(
+                Some("gzip, br"),
+                &[(Encoding::Gzip, None), (Encoding::Brotli, Some(900))]
+            ),
+            Encoding::Brotli
+        );
+        assert_eq!(negotiate_smallest(None, &available), Encoding::Identity);
+    }
+}
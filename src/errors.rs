@@ -2,24 +2,94 @@
 
 // dependencies
 use std::fmt;
+use std::path::PathBuf;
 
 // struct type to represent an error from the static file server
 #[derive(Debug)]
 pub enum ServeError {
     NotFound,
+    InvalidPath,
+    Forbidden,
+    RangeNotSatisfiable { size: u64 },
+    Denied { status: u16 },
+    Redirect { status: u16, location: String },
+    Timeout,
+    TooManyOpenStreams,
+    MethodNotAllowed { method: String },
+    TooLarge { size: u64, limit: u64 },
     Io(std::io::Error),
 }
 
+// methods for the ServeError type
+impl ServeError {
+    // map an IO error, surfacing permission problems as Forbidden
+    pub fn from_io(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => ServeError::Forbidden,
+            _ => ServeError::Io(err),
+        }
+    }
+
+    // the HTTP status code this error is best reported as
+    pub fn status_code(&self) -> u16 {
+        match self {
+            ServeError::NotFound => 404,
+            ServeError::InvalidPath => 400,
+            ServeError::Forbidden => 403,
+            ServeError::RangeNotSatisfiable { .. } => 416,
+            ServeError::Denied { status } => *status,
+            ServeError::Redirect { status, .. } => *status,
+            ServeError::Timeout => 504,
+            ServeError::TooManyOpenStreams => 503,
+            ServeError::MethodNotAllowed { .. } => 405,
+            // a server-side limit on what it will send, not a fault of the request
+            ServeError::TooLarge { .. } => 500,
+            ServeError::Io(_) => 500,
+        }
+    }
+
+    // value for the Content-Range header of a 416 response, `bytes */<size>`; None for
+    // every other error
+    pub fn content_range(&self) -> Option<String> {
+        match self {
+            ServeError::RangeNotSatisfiable { size } => Some(format!("bytes */{}", size)),
+            _ => None,
+        }
+    }
+}
+
 // implement the Display trait for the ServeError type
 impl fmt::Display for ServeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ServeError::NotFound => write!(f, "File not found"),
+            ServeError::InvalidPath => write!(f, "Invalid request path"),
+            ServeError::Forbidden => write!(f, "Access forbidden"),
+            ServeError::RangeNotSatisfiable { size } => {
+                write!(f, "Range not satisfiable for {} byte file", size)
+            }
+            ServeError::Denied { status } => write!(f, "Request denied with status {}", status),
+            ServeError::Redirect { location, .. } => write!(f, "Redirect to {}", location),
+            ServeError::Timeout => write!(f, "Reading the file timed out"),
+            ServeError::TooManyOpenStreams => write!(f, "Too many open streams"),
+            ServeError::MethodNotAllowed { method } => {
+                write!(f, "Method {} not allowed", method)
+            }
+            ServeError::TooLarge { size, limit } => {
+                write!(f, "File of {} bytes exceeds the {} byte limit", size, limit)
+            }
             ServeError::Io(err) => write!(f, "IO error: {}", err),
         }
     }
 }
 
+// implement conversion from an invalid path into a ServeError
+impl From<InvalidPath> for ServeError {
+    fn from(_: InvalidPath) -> Self {
+        ServeError::InvalidPath
+    }
+}
+
 // implement the Error trait for the ServeError type
 impl std::error::Error for ServeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
@@ -28,4 +98,109 @@ impl std::error::Error for ServeError {
             _ => None,
         }
     }
-}
\ No newline at end of file
+}
+
+// enum type to represent a configuration the builder refuses to turn into a server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    // the mount path isn't an absolute URL path, or carries a query or fragment
+    InvalidMountPath(String),
+    // a configured header value is empty or holds control characters
+    InvalidHeaderValue { header: &'static str },
+    // index files are served but none are named
+    EmptyIndexFiles,
+    // a preconnect origin isn't of the form `scheme://host[:port]`
+    InvalidPreconnectOrigin(String),
+    // the default charset isn't a bare charset name
+    InvalidCharset(String),
+    // a precompressed sibling extension is empty, holds a separator or is set for identity
+    InvalidPrecompressedExtension(String),
+    // the root directory doesn't exist or can't be reached
+    RootDirNotFound(PathBuf),
+    // the root directory names something other than a directory
+    RootDirNotADirectory(PathBuf),
+}
+
+// implement the Display trait for the ConfigError type
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidMountPath(path) => write!(f, "Invalid mount path {:?}", path),
+            ConfigError::InvalidHeaderValue { header } => {
+                write!(f, "Invalid value for the {} header", header)
+            }
+            ConfigError::EmptyIndexFiles => {
+                write!(f, "serve_index is on but no index files are set")
+            }
+            ConfigError::InvalidPreconnectOrigin(origin) => {
+                write!(f, "Invalid preconnect origin {:?}", origin)
+            }
+            ConfigError::InvalidCharset(charset) => write!(f, "Invalid charset {:?}", charset),
+            ConfigError::InvalidPrecompressedExtension(extension) => {
+                write!(f, "Invalid precompressed file extension {:?}", extension)
+            }
+            ConfigError::RootDirNotFound(path) => {
+                write!(f, "Root directory {} not found", path.display())
+            }
+            ConfigError::RootDirNotADirectory(path) => {
+                write!(f, "Root directory {} is not a directory", path.display())
+            }
+        }
+    }
+}
+
+// implement the Error trait for the ConfigError type
+impl std::error::Error for ConfigError {}
+
+// struct type to represent a request path which could not be decoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidPath;
+
+// implement the Display trait for the InvalidPath type
+impl fmt::Display for InvalidPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid request path")
+    }
+}
+
+// implement the Error trait for the InvalidPath type
+impl std::error::Error for InvalidPath {}
+
+// enum type to represent why the detailed resolver could not map a request path to a file
+#[derive(Debug)]
+pub enum ResolveError {
+    // the request path is not under this server's mount path
+    MountMismatch,
+    // the request path is under the mount path but can't be served
+    Serve(ServeError),
+}
+
+// implement the Display trait for the ResolveError type
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::MountMismatch => write!(f, "Request path is outside the mount path"),
+            ResolveError::Serve(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// implement the Error trait for the ResolveError type
+impl std::error::Error for ResolveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResolveError::Serve(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// implement conversion from a ResolveError into a ServeError; a mount mismatch is a plain miss
+impl From<ResolveError> for ServeError {
+    fn from(err: ResolveError) -> Self {
+        match err {
+            ResolveError::MountMismatch => ServeError::NotFound,
+            ResolveError::Serve(err) => err,
+        }
+    }
+}
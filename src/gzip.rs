@@ -0,0 +1,97 @@
+// src/gzip.rs
+
+// dependencies
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{self, Read, Write};
+
+// compress a body into the gzip format (RFC 1952)
+pub fn gzip_encode(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .expect("writing to a Vec cannot fail")
+}
+
+// decompress a gzip body; with a limit, decoding stops with a `FileTooLarge` error as soon as
+// the output would grow past it, so a tiny bomb never balloons in memory
+pub fn gzip_decode(data: &[u8], limit: Option<u64>) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match limit {
+        Some(limit) => {
+            // read one byte past the limit to tell a body of exactly `limit` bytes from a
+            // larger one
+            GzDecoder::new(data)
+                .take(limit.saturating_add(1))
+                .read_to_end(&mut out)?;
+            if out.len() as u64 > limit {
+                return Err(exceeds_limit());
+            }
+        }
+        None => {
+            GzDecoder::new(data).read_to_end(&mut out)?;
+        }
+    }
+    Ok(out)
+}
+
+// helper function to build the error for output growing past the decode limit
+fn exceeds_limit() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::FileTooLarge,
+        "decompressed body exceeds the limit",
+    )
+}
+
+// the uncompressed size a gzip body declares in its trailer (modulo 2^32, and unverified
+// until the body is decoded)
+pub fn gzip_declared_size(data: &[u8]) -> Option<u64> {
+    let trailer = data
+        .len()
+        .checked_sub(4)
+        .and_then(|start| data.get(start..))?;
+    Some(u64::from(u32::from_le_bytes([
+        trailer[0], trailer[1], trailer[2], trailer[3],
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_text_and_binary() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(200);
+        let encoded = gzip_encode(text.as_bytes());
+        assert!(encoded.len() < text.len() / 5);
+        assert_eq!(gzip_decode(&encoded, None).unwrap(), text.as_bytes());
+
+        let binary: Vec<u8> = (0..70_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        assert_eq!(gzip_decode(&gzip_encode(&binary), None).unwrap(), binary);
+
+        assert_eq!(gzip_decode(&gzip_encode(b""), None).unwrap(), b"");
+    }
+
+    #[test]
+    fn rejects_corrupt_streams() {
+        assert!(gzip_decode(b"not gzip at all", None).is_err());
+
+        let mut encoded = gzip_encode(b"hello, hello, hello");
+        let crc = encoded.len() - 8;
+        encoded[crc] ^= 0xff;
+        assert!(gzip_decode(&encoded, None).is_err());
+    }
+
+    #[test]
+    fn decode_stops_at_the_limit() {
+        let encoded = gzip_encode(&vec![0u8; 100_000]);
+        let err = gzip_decode(&encoded, Some(1_000)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(gzip_declared_size(&encoded), Some(100_000));
+
+        // a body of exactly the limit still decodes
+        assert_eq!(gzip_decode(&encoded, Some(100_000)).unwrap().len(), 100_000);
+    }
+}
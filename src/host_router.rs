@@ -0,0 +1,139 @@
+// src/host_router.rs
+
+// dependencies
+use crate::static_server::StaticServer;
+use std::collections::HashMap;
+
+// struct type which dispatches requests to a static file server based on the Host header
+#[derive(Default)]
+pub struct HostRouter {
+    exact: HashMap<String, StaticServer>,
+    wildcards: Vec<(String, StaticServer)>,
+    default: Option<StaticServer>,
+}
+
+// methods for the HostRouter type
+impl HostRouter {
+    // create an empty host router, with no mappings and no default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // map a host pattern to a static file server; patterns are either an exact host name
+    // (example.com) or a wildcard subdomain (*.example.com), which matches any subdomain
+    // of example.com but not example.com itself
+    pub fn add_host(&mut self, pattern: &str, server: StaticServer) {
+        let pattern = normalize_host(pattern);
+
+        match pattern.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') => {
+                let suffix = suffix.to_string();
+                self.wildcards.retain(|(existing, _)| *existing != suffix);
+                self.wildcards.push((suffix, server));
+                // keep the most specific (longest) suffix first so it wins
+                self.wildcards
+                    .sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+            }
+            _ => {
+                self.exact.insert(pattern, server);
+            }
+        }
+    }
+
+    // set the static file server used when no host mapping matches
+    pub fn set_default(&mut self, server: StaticServer) {
+        self.default = Some(server);
+    }
+
+    // pick the static file server for the incoming Host header value
+    pub fn for_host(&self, host: &str) -> Option<&StaticServer> {
+        let host = normalize_host(host);
+
+        if let Some(server) = self.exact.get(&host) {
+            return Some(server);
+        }
+
+        self.wildcards
+            .iter()
+            .find(|(suffix, _)| host.len() > suffix.len() && host.ends_with(suffix.as_str()))
+            .map(|(_, server)| server)
+            .or(self.default.as_ref())
+    }
+}
+
+// helper function to normalize a Host header value: lowercase, no port, no trailing dot
+fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+
+    let without_port = if host.starts_with('[') {
+        // IPv6 literal, e.g. [::1]:8080
+        match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        }
+    } else {
+        match host.rsplit_once(':') {
+            Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host,
+        }
+    };
+
+    without_port.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StaticServerConfig;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn server_for(root: &std::path::Path) -> StaticServer {
+        StaticServer::from_config(StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: root.to_path_buf(),
+            serve_index: false,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn routes_two_hosts_to_two_roots() {
+        let site_a = tempdir().unwrap();
+        let site_b = tempdir().unwrap();
+        fs::write(site_a.path().join("hello.txt"), "from a").unwrap();
+        fs::write(site_b.path().join("hello.txt"), "from b").unwrap();
+
+        let mut router = HostRouter::new();
+        router.add_host("a.example.com", server_for(site_a.path()));
+        router.add_host("b.example.com", server_for(site_b.path()));
+
+        let a = router.for_host("a.example.com").unwrap();
+        assert_eq!(a.read_file("/static/hello.txt").unwrap().body, b"from a");
+
+        let b = router.for_host("B.Example.com:8080").unwrap();
+        assert_eq!(b.read_file("/static/hello.txt").unwrap().body, b"from b");
+
+        assert!(router.for_host("c.example.com").is_none());
+    }
+
+    #[test]
+    fn wildcard_subdomains_and_default() {
+        let tenants = tempdir().unwrap();
+        let fallback = tempdir().unwrap();
+
+        let mut router = HostRouter::new();
+        router.add_host("*.example.com", server_for(tenants.path()));
+        router.set_default(server_for(fallback.path()));
+
+        let tenant = router.for_host("shop.example.com").unwrap();
+        assert_eq!(tenant.root_dir(), tenants.path());
+
+        let nested = router.for_host("eu.shop.example.com").unwrap();
+        assert_eq!(nested.root_dir(), tenants.path());
+
+        // the bare domain is not covered by the wildcard, so the default applies
+        let bare = router.for_host("example.com").unwrap();
+        assert_eq!(bare.root_dir(), fallback.path());
+    }
+}
@@ -0,0 +1,143 @@
+// src/html.rs
+
+// helper function to escape text for use inside HTML content or a quoted attribute
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// insert a snippet right after the opening <head> tag; returns None when the document has no <head>
+pub fn insert_into_head(body: &[u8], snippet: &str) -> Option<Vec<u8>> {
+    let head_start = find_tag(body, b"<head")?;
+    let head_end = head_start + body[head_start..].iter().position(|&b| b == b'>')? + 1;
+
+    let mut injected = Vec::with_capacity(body.len() + snippet.len());
+    injected.extend_from_slice(&body[..head_end]);
+    injected.extend_from_slice(snippet.as_bytes());
+    injected.extend_from_slice(&body[head_end..]);
+    Some(injected)
+}
+
+// add a <base href> to the document head, leaving documents which already have one untouched
+pub fn inject_base_href(body: &[u8], href: &str) -> Option<Vec<u8>> {
+    if find_tag(body, b"<base").is_some() {
+        return None;
+    }
+
+    insert_into_head(body, &format!(r#"<base href="{}">"#, escape_html(href)))
+}
+
+// render `<meta property="..." content="...">` tags for property/content pairs
+pub fn meta_tags(pairs: &[(String, String)]) -> String {
+    pairs
+        .iter()
+        .map(|(property, content)| {
+            format!(
+                r#"<meta property="{}" content="{}">"#,
+                escape_html(property),
+                escape_html(content)
+            )
+        })
+        .collect()
+}
+
+// struct type which represents one link of an HTML directory listing, with the href already
+// URL-encoded; a symlink carries its target, which is shown after the link
+pub struct ListingLink {
+    pub href: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub target: Option<String>,
+}
+
+// render an HTML directory listing, escaping names and targets here. A parent link is added
+// unless `parent` is None
+pub fn directory_listing(
+    title_path: &str,
+    parent: Option<&str>,
+    entries: &[ListingLink],
+) -> String {
+    let title = escape_html(title_path);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body><h1>Index of {title}</h1>\n<ul>\n"
+    );
+    if let Some(parent) = parent {
+        html.push_str(&format!(
+            "<li><a href=\"{}\">../</a></li>\n",
+            escape_html(parent)
+        ));
+    }
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        match &entry.target {
+            Some(target) => html.push_str(&format!(
+                "<li class=\"symlink\"><a href=\"{}\">{}{}</a> &rarr; {}</li>\n",
+                escape_html(&entry.href),
+                escape_html(&entry.name),
+                suffix,
+                escape_html(target)
+            )),
+            None => html.push_str(&format!(
+                "<li><a href=\"{}\">{}{}</a></li>\n",
+                escape_html(&entry.href),
+                escape_html(&entry.name),
+                suffix
+            )),
+        }
+    }
+    html.push_str("</ul>\n</body></html>\n");
+    html
+}
+
+// helper function to find an opening tag by name, ignoring ASCII case
+fn find_tag(body: &[u8], tag: &[u8]) -> Option<usize> {
+    body.windows(tag.len() + 1).position(|window| {
+        window[..tag.len()].eq_ignore_ascii_case(tag)
+            && matches!(
+                window[tag.len()],
+                b'>' | b' ' | b'\t' | b'\n' | b'\r' | b'/'
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_href_goes_after_head_and_is_idempotent() {
+        let html = b"<html><HEAD lang=\"en\"><title>x</title></HEAD></html>";
+        let injected = inject_base_href(html, "/static/").unwrap();
+        assert_eq!(
+            injected,
+            b"<html><HEAD lang=\"en\"><base href=\"/static/\"><title>x</title></HEAD></html>"
+        );
+
+        assert!(inject_base_href(&injected, "/static/").is_none());
+        assert!(inject_base_href(b"<p>no head</p>", "/static/").is_none());
+        // a <header> element is not the document head
+        assert!(inject_base_href(b"<header>x</header>", "/").is_none());
+    }
+
+    #[test]
+    fn meta_tags_are_escaped() {
+        let pairs = vec![
+            ("og:title".to_string(), "Tom & \"Jerry\"".to_string()),
+            ("og:type".to_string(), "website".to_string()),
+        ];
+        assert_eq!(
+            meta_tags(&pairs),
+            r#"<meta property="og:title" content="Tom &amp; &quot;Jerry&quot;"><meta property="og:type" content="website">"#
+        );
+    }
+}
@@ -1,11 +1,54 @@
 // src/lib.rs
 
 // module declarations
+pub mod accept;
+pub mod access_log;
+pub mod builder;
+pub mod cache;
+pub mod client_hints;
+pub mod conditional;
 pub mod config;
+pub mod diff;
+pub mod encoding;
 pub mod errors;
+pub mod gzip;
+pub mod hash;
+pub mod host_router;
+mod html;
+pub mod listing;
+pub mod negative_cache;
+pub mod percent;
+pub mod pre_serve;
+pub mod range;
+pub mod router;
 pub mod static_server;
+pub mod stream;
+mod symlink_cache;
+mod tar;
+pub mod transform;
+pub mod vfs;
 
 // re-exports
+pub use accept::*;
+pub use access_log::*;
+pub use builder::*;
+pub use cache::*;
+pub use client_hints::*;
+pub use conditional::*;
 pub use config::*;
+pub use diff::*;
+pub use encoding::*;
 pub use errors::*;
+pub use gzip::*;
+pub use hash::*;
+pub use host_router::*;
+pub use listing::*;
+pub use negative_cache::*;
+pub use percent::*;
+pub use pre_serve::*;
+pub use range::*;
+pub use router::*;
 pub use static_server::*;
+pub use stream::*;
+pub use transform::*;
+pub use vfs::*;
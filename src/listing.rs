@@ -0,0 +1,125 @@
+// src/listing.rs
+
+// dependencies
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// enum type which represents the kind of a directory entry, as reported by its own
+// metadata rather than that of a symlink's target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl FileType {
+    // the name used for the type in JSON listings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileType::File => "file",
+            FileType::Dir => "dir",
+            FileType::Symlink => "symlink",
+        }
+    }
+}
+
+// struct type which represents one entry of a directory listing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntryInfo {
+    pub name: String,
+    // whether the entry, or the file a symlink points at, is a directory
+    pub is_dir: bool,
+    pub file_type: FileType,
+    // for a symlink, the root-relative path of its target
+    pub target: Option<String>,
+    // length in bytes; 0 for directories
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+// sort entries into the canonical listing order: directories first, then by name, comparing
+// the names byte by byte so case matters and the order is the same on every platform
+pub fn sort_entries(entries: &mut [DirEntryInfo]) {
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+}
+
+// render entries as a JSON array of `{"name", "is_dir", "file_type", "target", "size",
+// "modified"}` objects, with `target` null for anything but a symlink and `modified` in whole
+// seconds since the Unix epoch (or null when unknown)
+pub fn entries_to_json(entries: &[DirEntryInfo]) -> String {
+    let objects: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let modified = entry
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or("null".to_string(), |since| since.as_secs().to_string());
+            let target = entry
+                .target
+                .as_deref()
+                .map_or("null".to_string(), json_string);
+            format!(
+                r#"{{"name":{},"is_dir":{},"file_type":"{}","target":{},"size":{},"modified":{}}}"#,
+                json_string(&entry.name),
+                entry.is_dir,
+                entry.file_type.as_str(),
+                target,
+                entry.size,
+                modified
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+// helper function to quote a string as a JSON string literal
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if u32::from(c) < 0x20 => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn renders_entries_as_json() {
+        let entries = vec![
+            DirEntryInfo {
+                name: "say \"hi\"\n.txt".to_string(),
+                is_dir: false,
+                file_type: FileType::Symlink,
+                target: Some("releases/v1.txt".to_string()),
+                size: 12,
+                modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            },
+            DirEntryInfo {
+                name: "sub".to_string(),
+                is_dir: true,
+                file_type: FileType::Dir,
+                target: None,
+                size: 0,
+                modified: None,
+            },
+        ];
+        assert_eq!(
+            entries_to_json(&entries),
+            r#"[{"name":"say \"hi\"\n.txt","is_dir":false,"file_type":"symlink","target":"releases/v1.txt","size":12,"modified":1700000000},{"name":"sub","is_dir":true,"file_type":"dir","target":null,"size":0,"modified":null}]"#
+        );
+        assert_eq!(entries_to_json(&[]), "[]");
+    }
+}
@@ -0,0 +1,146 @@
+// src/negative_cache.rs
+
+// dependencies
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// struct type which represents a snapshot of the negative cache counters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NegativeCacheStats {
+    // lookups answered as a miss without touching the filesystem
+    pub hits: u64,
+    // lookups which had to resolve the path
+    pub misses: u64,
+    pub entries: usize,
+}
+
+// struct type which remembers request paths that recently resolved to nothing, so a burst of
+// 404s for the same path skips the stat and canonicalize calls. Tradeoff: a file created
+// within the TTL stays missing until the entry expires or is invalidated
+pub(crate) struct NegativeCache {
+    ttl: Duration,
+    capacity: usize,
+    inner: Mutex<NegativeInner>,
+}
+
+// struct type which represents the mutable state of the negative cache
+#[derive(Default)]
+struct NegativeInner {
+    // when each path was stored, plus an insertion counter to order same-instant entries
+    entries: HashMap<String, (Instant, u64)>,
+    clock: u64,
+    stats: NegativeCacheStats,
+}
+
+// methods for the NegativeCache type
+impl NegativeCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        NegativeCache {
+            ttl,
+            capacity,
+            inner: Mutex::new(NegativeInner::default()),
+        }
+    }
+
+    // whether a request path is known to be missing
+    pub fn contains(&self, request_path: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let fresh = match inner.entries.get(request_path) {
+            Some((stored, _)) if stored.elapsed() < self.ttl => true,
+            Some(_) => {
+                inner.entries.remove(request_path);
+                false
+            }
+            None => false,
+        };
+
+        if fresh {
+            inner.stats.hits += 1;
+        } else {
+            inner.stats.misses += 1;
+        }
+        fresh
+    }
+
+    // remember a request path as missing; when full, expired entries go first and then the
+    // oldest one, so the map never grows past its capacity
+    pub fn insert(&self, request_path: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let grows = !inner.entries.contains_key(request_path);
+        if grows && inner.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            inner
+                .entries
+                .retain(|_, (stored, _)| stored.elapsed() < ttl);
+        }
+        if grows
+            && inner.entries.len() >= self.capacity
+            && let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, order))| *order)
+                .map(|(path, _)| path.clone())
+        {
+            inner.entries.remove(&oldest);
+        }
+        inner.clock += 1;
+        let order = inner.clock;
+        inner
+            .entries
+            .insert(request_path.to_string(), (Instant::now(), order));
+    }
+
+    // forget a single request path, returning whether it was cached
+    pub fn remove(&self, request_path: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.entries.remove(request_path).is_some()
+    }
+
+    // forget every cached miss, e.g. after a file watcher saw files appear
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.entries.clear();
+    }
+
+    // a snapshot of the counters and current occupancy
+    pub fn stats(&self) -> NegativeCacheStats {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        NegativeCacheStats {
+            entries: inner.entries.len(),
+            ..inner.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_entries_and_stays_within_capacity() {
+        let cache = NegativeCache::new(Duration::from_secs(60), 2);
+        cache.insert("/a");
+        cache.insert("/b");
+        cache.insert("/c");
+        assert_eq!(cache.stats().entries, 2);
+        assert!(!cache.contains("/a"));
+        assert!(cache.contains("/c"));
+
+        let expired = NegativeCache::new(Duration::ZERO, 8);
+        expired.insert("/a");
+        assert!(!expired.contains("/a"));
+        assert_eq!(
+            expired.stats(),
+            NegativeCacheStats {
+                hits: 0,
+                misses: 1,
+                entries: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,107 @@
+// src/percent.rs
+
+// dependencies
+use crate::errors::InvalidPath;
+
+// decode %XX escapes in a request path into UTF-8; characters that are not part of an escape
+// are kept as they are, while truncated escapes, non-hex digits, an escaped NUL byte and byte
+// sequences that are not valid UTF-8 are rejected
+pub fn percent_decode_path(s: &str) -> Result<String, InvalidPath> {
+    if !s.contains('%') {
+        return Ok(s.to_string());
+    }
+
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let high = bytes.get(i + 1).copied().and_then(hex_value);
+            let low = bytes.get(i + 2).copied().and_then(hex_value);
+            match (high, low) {
+                // a NUL would truncate the path at the OS boundary
+                (Some(0), Some(0)) => return Err(InvalidPath),
+                (Some(high), Some(low)) => decoded.push(high << 4 | low),
+                _ => return Err(InvalidPath),
+            }
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| InvalidPath)
+}
+
+// encode a single path segment (a file name) for use in a URL; everything but the unreserved
+// characters of RFC 3986 is escaped, `/` included
+pub fn percent_encode_segment(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// helper function to turn an ASCII hex digit into its value
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_valid_escapes() {
+        assert_eq!(percent_decode_path("my%20file.txt").unwrap(), "my file.txt");
+        assert_eq!(percent_decode_path("caf%C3%A9").unwrap(), "café");
+        assert_eq!(percent_decode_path("%2e%2E").unwrap(), "..");
+        assert_eq!(
+            percent_decode_path("plain/path.css").unwrap(),
+            "plain/path.css"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_escapes() {
+        assert_eq!(percent_decode_path("%ZZ"), Err(InvalidPath));
+        assert_eq!(percent_decode_path("trailing%2"), Err(InvalidPath));
+        assert_eq!(percent_decode_path("trailing%"), Err(InvalidPath));
+        // a lone continuation byte is not UTF-8
+        assert_eq!(percent_decode_path("%A9"), Err(InvalidPath));
+        assert_eq!(percent_decode_path("secret.txt%00.png"), Err(InvalidPath));
+    }
+
+    #[test]
+    fn encodes_segments_and_round_trips() {
+        assert_eq!(percent_encode_segment("a b/c?.txt"), "a%20b%2Fc%3F.txt");
+        for name in ["café <1>.html", "100% done", "~user_file-1.md"] {
+            assert_eq!(
+                percent_decode_path(&percent_encode_segment(name)).unwrap(),
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_already_decoded_characters_in_mixed_input() {
+        assert_eq!(
+            percent_decode_path("café/my%20notes+draft.txt").unwrap(),
+            "café/my notes+draft.txt"
+        );
+        assert_eq!(percent_decode_path("100%25 done").unwrap(), "100% done");
+    }
+}
@@ -0,0 +1,43 @@
+// src/pre_serve.rs
+
+// dependencies
+use crate::static_server::StaticFile;
+use std::path::Path;
+
+// type alias for a user supplied hook consulted after resolution and before a file is read;
+// it receives the resolved path on disk and the caller's request context
+pub type PreServeHook = Box<dyn Fn(&Path, &RequestContext) -> PreServeDecision + Send + Sync>;
+
+// struct type which represents request data the caller hands to the pre-serve hook
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    pub headers: Vec<(String, String)>,
+}
+
+// methods for the RequestContext type
+impl RequestContext {
+    // add a header to the context
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    // the value of the first header with the given name, ignoring ASCII case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// enum type which represents the verdict of the pre-serve hook
+#[derive(Debug)]
+pub enum PreServeDecision {
+    // serve the file as usual
+    Allow,
+    // refuse the request with the given HTTP status
+    Deny(u16),
+    // respond with this file instead
+    Replace(StaticFile),
+}
@@ -0,0 +1,146 @@
+// src/range.rs
+
+// dependencies
+use crate::errors::ServeError;
+use std::borrow::Cow;
+
+// struct type which represents the byte range that was served from a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeMeta {
+    // first byte served, inclusive
+    pub start: u64,
+    // last byte served, inclusive
+    pub end: u64,
+    // total size of the file
+    pub size: u64,
+    pub mime_type: Cow<'static, str>,
+}
+
+// methods for the RangeMeta type
+impl RangeMeta {
+    // number of bytes in the range
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    // whether the range covers no bytes; a satisfiable range never does
+    pub fn is_empty(&self) -> bool {
+        self.end < self.start
+    }
+
+    // value for the Content-Range header of a 206 response
+    pub fn content_range(&self) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, self.size)
+    }
+}
+
+// struct type which represents the bytes of one range read from a file, for a 206 response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticFileRange {
+    pub body: Vec<u8>,
+    pub meta: RangeMeta,
+}
+
+// parse a single `bytes=` range header value against a file of the given size, returning the
+// inclusive start and end offsets; supports `bytes=0-1023`, open-ended `bytes=500-` and
+// suffix `bytes=-500` forms. A malformed header, or one asking for several ranges, is
+// ignored (None) so the full body is sent, while a well-formed range lying wholly past the
+// end of the file is not satisfiable
+pub fn parse_range(range: &str, size: u64) -> Result<Option<(u64, u64)>, ServeError> {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    // multiple ranges are not supported
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let (first, last) = (first.trim(), last.trim());
+    let Ok(bounds) = parse_bounds(first, last) else {
+        return Ok(None);
+    };
+
+    let (start, end) = match bounds {
+        // bytes=-500: the final 500 bytes
+        (None, Some(suffix)) => {
+            if suffix == 0 || size == 0 {
+                return Err(unsatisfiable_for(size));
+            }
+            (size.saturating_sub(suffix), size - 1)
+        }
+        // bytes=500-: everything from byte 500
+        (Some(start), None) => (start, size.saturating_sub(1)),
+        // bytes=0-1023
+        (Some(start), Some(end)) => (start, end.min(size.saturating_sub(1))),
+        (None, None) => return Ok(None),
+    };
+
+    if start >= size {
+        return Err(unsatisfiable_for(size));
+    }
+
+    Ok(Some((start, end)))
+}
+
+// helper function to parse the two sides of a range spec, either of which may be empty; a
+// last byte before the first makes the spec malformed
+fn parse_bounds(first: &str, last: &str) -> Result<(Option<u64>, Option<u64>), ()> {
+    let parse = |bound: &str| match bound {
+        "" => Ok(None),
+        _ if bound.bytes().all(|b| b.is_ascii_digit()) => bound.parse().map(Some).map_err(|_| ()),
+        _ => Err(()),
+    };
+    let (first, last) = (parse(first)?, parse(last)?);
+    match (first, last) {
+        (Some(start), Some(end)) if end < start => Err(()),
+        bounds => Ok(bounds),
+    }
+}
+
+// helper function to build the not satisfiable error for a file size
+fn unsatisfiable_for(size: u64) -> ServeError {
+    ServeError::RangeNotSatisfiable { size }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_range_form() {
+        assert_eq!(parse_range("bytes=0-9", 100).unwrap(), Some((0, 9)));
+        assert_eq!(parse_range("bytes=90-", 100).unwrap(), Some((90, 99)));
+        assert_eq!(parse_range("bytes=-10", 100).unwrap(), Some((90, 99)));
+        // ranges running past the end are clamped
+        assert_eq!(parse_range("bytes=50-500", 100).unwrap(), Some((50, 99)));
+        assert_eq!(parse_range("bytes=-500", 100).unwrap(), Some((0, 99)));
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        for range in ["bytes=100-", "bytes=100-200", "bytes=-0"] {
+            assert!(matches!(
+                parse_range(range, 100),
+                Err(ServeError::RangeNotSatisfiable { size: 100 })
+            ));
+        }
+    }
+
+    #[test]
+    fn ignores_malformed_and_multiple_ranges() {
+        for range in [
+            "bytes=9-0",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=-",
+            "bytes=0-1,5-6",
+            "bytes=0-1,200-300",
+            "items=0-1",
+            "oops",
+        ] {
+            assert_eq!(parse_range(range, 100).unwrap(), None, "{range}");
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// src/router.rs
+
+// dependencies
+use crate::errors::{ResolveError, ServeError};
+use crate::static_server::{StaticFile, StaticServer};
+use std::path::PathBuf;
+
+// struct type which dispatches requests across several static file servers by mount path
+#[derive(Default)]
+pub struct StaticRouter {
+    servers: Vec<StaticServer>,
+}
+
+// methods for the StaticRouter type
+impl StaticRouter {
+    // create an empty router
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // add a static file server; the most specific (longest) mount path is tried first
+    pub fn add(&mut self, server: StaticServer) {
+        self.servers.push(server);
+        self.servers
+            .sort_by_key(|server| std::cmp::Reverse(server.mount_path().len()));
+    }
+
+    // resolve a request path using the first server whose mount path covers it
+    pub fn resolve(&self, request_path: &str) -> Result<PathBuf, ServeError> {
+        for server in &self.servers {
+            match server.resolve_detailed(request_path) {
+                // not this server's prefix, so give the next mount a chance
+                Err(ResolveError::MountMismatch) => continue,
+                outcome => return outcome.map_err(ServeError::from),
+            }
+        }
+
+        Err(ServeError::NotFound)
+    }
+
+    // read a file using the first server whose mount path covers the request path; a miss
+    // inside a matching mount is a real NotFound and does not fall through to other mounts
+    pub fn read_file(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        for server in &self.servers {
+            match server.read_file_detailed(request_path) {
+                Err(ResolveError::MountMismatch) => continue,
+                outcome => return outcome.map_err(ServeError::from),
+            }
+        }
+
+        Err(ServeError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn tries_the_next_mount_on_mismatch_only() {
+        let assets = tempdir().unwrap();
+        let admin = tempdir().unwrap();
+        fs::write(assets.path().join("site.css"), "site").unwrap();
+        fs::write(assets.path().join("shared.css"), "shared").unwrap();
+        fs::write(admin.path().join("admin.css"), "admin").unwrap();
+
+        let mut router = StaticRouter::new();
+        router.add(
+            StaticServer::builder(assets.path())
+                .mount_path("/static")
+                .build()
+                .unwrap(),
+        );
+        router.add(
+            StaticServer::builder(admin.path())
+                .mount_path("/static/admin")
+                .build()
+                .unwrap(),
+        );
+
+        assert_eq!(router.read_file("/static/site.css").unwrap().body, b"site");
+        assert_eq!(
+            router.read_file("/static/admin/admin.css").unwrap().body,
+            b"admin"
+        );
+
+        // inside the admin mount, a miss is final rather than falling back to /static
+        assert!(matches!(
+            router.read_file("/static/admin/../shared.css"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            router.read_file("/static/admin/missing.css"),
+            Err(ServeError::NotFound)
+        ));
+
+        // nothing is mounted here at all
+        assert!(matches!(
+            router.resolve("/other/site.css"),
+            Err(ServeError::NotFound)
+        ));
+    }
+}
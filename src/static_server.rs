@@ -1,25 +1,188 @@
 // src/static_server.rs
 
 // dependencies
-use crate::config::StaticServerConfig;
-use crate::errors::ServeError;
-use std::fs::canonicalize;
+use crate::accept::prefers_json;
+use crate::access_log::{AccessLogRecord, AccessLogSink};
+use crate::builder::StaticServerBuilder;
+use crate::cache::{CacheStats, FileCache, FileSource};
+use crate::client_hints::{ClientHints, is_save_data_on};
+use crate::conditional::{etag_matches, not_modified_since};
+use crate::config::{
+    BareDirectoryPolicy, COEP_VALUES, CORP_VALUES, DEFAULT_MAX_DECOMPRESSED_SIZE, IndexSelection,
+    MountRootPolicy, ServiceWorkerConfig, StaticServerConfig, is_valid_charset,
+    is_valid_header_value, is_valid_origin, is_valid_sibling_extension,
+};
+use crate::diff::RootDiff;
+use crate::encoding::{Encoding, negotiate_encoding, negotiate_smallest};
+use crate::errors::{ConfigError, ResolveError, ServeError};
+use crate::gzip::{gzip_declared_size, gzip_decode, gzip_encode};
+use crate::hash::{DigestAlgo, SriAlgo, content_digest, fnv1a_64, sri_integrity};
+use crate::html::{self, ListingLink};
+use crate::listing::{DirEntryInfo, FileType, entries_to_json, sort_entries};
+use crate::negative_cache::{NegativeCache, NegativeCacheStats};
+use crate::percent::{percent_decode_path, percent_encode_segment};
+use crate::pre_serve::{PreServeDecision, PreServeHook, RequestContext};
+use crate::range::{RangeMeta, StaticFileRange, parse_range};
+use crate::stream::{StaticFileStream, StreamGuard};
+use crate::symlink_cache::SymlinkCache;
+use crate::tar::TarWriter;
+use crate::transform::{HtmlMetaInjector, TransformHook};
+#[cfg(feature = "tokio")]
+use crate::vfs::{AsyncVfs, TokioFs};
+use crate::vfs::{StdFs, Vfs};
 use std::borrow::Cow;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// request path prefix of ACME HTTP-01 challenges
+const ACME_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+
+// Cache-Control sent for content-hashed files when immutable_for_hashed is on
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
 
 // struct type which represents the static file server
 pub struct StaticServer {
     mount_path: String,
     root_dir: PathBuf,
+    // the root directory resolved once at construction, so requests don't canonicalize it
+    // again; None when the root didn't exist yet, in which case each request resolves it
+    canonical_root: Option<PathBuf>,
     serve_index: bool,
+    max_path_len: Option<usize>,
+    serve_hidden: bool,
+    nosniff: bool,
+    follow_symlinks: bool,
+    per_tenant_jail: bool,
+    open_nofollow: bool,
+    inject_base_href: bool,
+    index_files: Vec<String>,
+    index_selection: IndexSelection,
+    bare_directory: BareDirectoryPolicy,
+    mount_root: MountRootPolicy,
+    no_index_prefixes: Vec<String>,
+    force_mime_for_prefix: Vec<(String, String)>,
+    mime_overrides: HashMap<String, String>,
+    // extensions lowercased and without a leading dot
+    allowed_extensions: Option<HashSet<String>>,
+    denied_extensions: HashSet<String>,
+    sniff_table: Vec<(Vec<u8>, String)>,
+    max_file_size: Option<u64>,
+    serve_lone_gzip: bool,
+    max_decompressed_size: Option<u64>,
+    allowed_referers: Option<Vec<String>>,
+    allow_missing_referer: bool,
+    canonical_host: Option<String>,
+    detect_charset: bool,
+    default_charset: Option<String>,
+    try_files: Vec<String>,
+    spa_fallback: bool,
+    directory_listing: bool,
+    preconnect_origins: Vec<String>,
+    // the extension appended to extension-less paths, when clean URLs are on
+    clean_url_extension: Option<String>,
+    forbidden_page: Option<PathBuf>,
+    not_found_file: Option<PathBuf>,
+    error_pages: HashMap<u16, PathBuf>,
+    build_id: Option<String>,
+    fixed_last_modified: Option<SystemTime>,
+    combined_index_etag: bool,
+    symlink_cache: Option<SymlinkCache>,
+    negative_cache: Option<NegativeCache>,
+    acme_challenge_dir: Option<PathBuf>,
+    content_location: bool,
+    cache_control: Option<String>,
+    immutable_for_hashed: bool,
+    alt_svc: Option<String>,
+    timing_allow_origin: Option<String>,
+    cross_origin_resource_policy: Option<String>,
+    cross_origin_embedder_policy: Option<String>,
+    service_worker: Option<ServiceWorkerConfig>,
+    content_digest: Option<DigestAlgo>,
+    compress: bool,
+    compress_min_size: usize,
+    // suffix, with its leading dot, of the precompressed sibling in each coding
+    precompressed_extensions: HashMap<Encoding, String>,
+    max_open_streams: Option<usize>,
+    open_streams: Arc<AtomicUsize>,
+    cache: Option<FileCache>,
+    access_log: Option<AccessLogSink>,
+    pre_serve: Option<PreServeHook>,
+    transform: Option<TransformHook>,
+    html_meta_injector: Option<HtmlMetaInjector>,
+    #[cfg(feature = "tokio")]
+    read_timeout: Option<Duration>,
+    vfs: Arc<dyn Vfs>,
+    #[cfg(feature = "tokio")]
+    async_vfs: Arc<dyn AsyncVfs>,
 }
 
 // struct type which represents the static file to be served
 #[derive(Debug)]
 pub struct StaticFile {
     pub body: Vec<u8>,
+    // the length of the full body in bytes; equal to body.len() except for HEAD responses,
+    // whose body is empty
+    pub len: u64,
+    pub mime_type: Cow<'static, str>,
+    pub path: PathBuf,
+    pub headers: Vec<(&'static str, String)>,
+    // the HTTP status to respond with; 200 unless an error page is being served
+    pub status: u16,
+    // the entity tag to send in the ETag header, when one is available
+    pub etag: Option<String>,
+    // the file's modification time, for the Last-Modified header
+    pub modified: Option<SystemTime>,
+    // the Content-Encoding to send, when the body is not the identity representation
+    pub content_encoding: Option<Cow<'static, str>>,
+    // whether the body was read from disk or served from the cache
+    pub source: FileSource,
+}
+
+// struct type which represents what is known about an opened file without reading it
+#[derive(Clone, Debug)]
+pub struct FileMeta {
+    pub len: u64,
     pub mime_type: Cow<'static, str>,
+    pub modified: Option<SystemTime>,
     pub path: PathBuf,
+    pub etag: Option<String>,
+    // the open-stream slot held for the file; shared between clones and released once the
+    // last one is dropped
+    pub guard: Option<Arc<StreamGuard>>,
+}
+
+// struct type which represents what stat reports about a file: its size on disk, the MIME
+// type it would be served with and its modification time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaticFileMeta {
+    pub size: u64,
+    pub mime_type: Cow<'static, str>,
+    pub modified: Option<SystemTime>,
+}
+
+// struct type which represents a request path resolved onto the filesystem
+struct Resolved {
+    path: PathBuf,
+    // the mount-relative path of the served file, including any index file name
+    relative: String,
+    // whether the path was reached through index resolution of a directory
+    is_index: bool,
+}
+
+// enum type which represents how directory requests are treated during resolution
+#[derive(Clone, Copy, Debug)]
+enum IndexMode {
+    // serve the index of a directory when serve_index is on
+    Auto,
+    // the path must be a directory, and its index is served
+    Required,
+    // the path must be a file
+    Never,
 }
 
 // methods for the StaticServer type
@@ -27,191 +190,6326 @@ impl StaticServer {
     // create a static file server from it's configuration values
     pub fn from_config(config: StaticServerConfig) -> Self {
         let mount_path = normalize_mount_path(config.mount_path.as_ref());
+        let canonical_root = StdFs.canonicalize(&config.root_dir).ok();
         StaticServer {
             mount_path,
             root_dir: config.root_dir,
+            canonical_root,
             serve_index: config.serve_index,
+            max_path_len: config.max_path_len,
+            serve_hidden: config.serve_hidden,
+            nosniff: config.nosniff,
+            follow_symlinks: config.follow_symlinks,
+            per_tenant_jail: config.per_tenant_jail,
+            open_nofollow: config.open_nofollow,
+            inject_base_href: config.inject_base_href,
+            index_files: {
+                let mut index_files = config.index_files;
+                index_files.truncate(config.max_index_candidates.max(1));
+                index_files
+            },
+            index_selection: config.index_selection,
+            bare_directory: config.bare_directory,
+            mount_root: config.mount_root,
+            no_index_prefixes: config
+                .no_index_prefixes
+                .iter()
+                .map(|prefix| prefix.trim_matches('/').to_string())
+                .collect(),
+            force_mime_for_prefix: config
+                .force_mime_for_prefix
+                .into_iter()
+                .map(|(prefix, mime_type)| (prefix.trim_matches('/').to_string(), mime_type))
+                .collect(),
+            mime_overrides: config
+                .mime_overrides
+                .into_iter()
+                .map(|(extension, mime_type)| {
+                    (
+                        extension.trim_start_matches('.').to_ascii_lowercase(),
+                        mime_type,
+                    )
+                })
+                .collect(),
+            allowed_extensions: config.allowed_extensions.map(normalize_extensions),
+            denied_extensions: normalize_extensions(config.denied_extensions),
+            sniff_table: config.sniff_table,
+            max_file_size: config.max_file_size,
+            serve_lone_gzip: config.serve_lone_gzip,
+            max_decompressed_size: config.max_decompressed_size,
+            allowed_referers: config.allowed_referers.map(|hosts| {
+                hosts
+                    .iter()
+                    .map(|host| host.trim().to_ascii_lowercase())
+                    .collect()
+            }),
+            allow_missing_referer: config.allow_missing_referer,
+            canonical_host: config.canonical_host,
+            detect_charset: config.detect_charset,
+            default_charset: config
+                .default_charset
+                .filter(|charset| is_valid_charset(charset)),
+            try_files: config.try_files,
+            spa_fallback: config.spa_fallback,
+            directory_listing: config.directory_listing,
+            preconnect_origins: config
+                .preconnect_origins
+                .into_iter()
+                .filter(|origin| is_valid_origin(origin))
+                .collect(),
+            clean_url_extension: config.clean_urls.then(|| {
+                config
+                    .clean_url_extension
+                    .trim_start_matches('.')
+                    .to_string()
+            }),
+            forbidden_page: config.forbidden_page,
+            not_found_file: config.not_found_file,
+            error_pages: config.error_pages,
+            build_id: config.build_id,
+            fixed_last_modified: config.fixed_last_modified,
+            combined_index_etag: config.combined_index_etag,
+            symlink_cache: config
+                .symlink_cache_ttl
+                .map(|ttl| SymlinkCache::new(ttl, config.symlink_cache_capacity)),
+            negative_cache: config
+                .negative_cache_ttl
+                .map(|ttl| NegativeCache::new(ttl, config.negative_cache_capacity)),
+            acme_challenge_dir: config.acme_challenge_dir,
+            content_location: config.content_location,
+            immutable_for_hashed: config.immutable_for_hashed,
+            cache_control: config.cache_control.map(|directive| {
+                compose_cache_control(
+                    directive,
+                    config.stale_while_revalidate,
+                    config.stale_if_error,
+                )
+            }),
+            alt_svc: config
+                .alt_svc
+                .filter(|alt_svc| is_valid_header_value(alt_svc)),
+            timing_allow_origin: config
+                .timing_allow_origin
+                .filter(|origin| is_valid_header_value(origin)),
+            cross_origin_resource_policy: config
+                .cross_origin_resource_policy
+                .filter(|policy| CORP_VALUES.contains(&policy.as_str())),
+            cross_origin_embedder_policy: config
+                .cross_origin_embedder_policy
+                .filter(|policy| COEP_VALUES.contains(&policy.as_str())),
+            service_worker: config.service_worker.map(|worker| ServiceWorkerConfig {
+                path: worker.path.trim_matches('/').to_string(),
+                scope: worker.scope,
+            }),
+            content_digest: config.content_digest,
+            compress: config.compress,
+            compress_min_size: config.compress_min_size,
+            precompressed_extensions: config
+                .precompressed_extensions
+                .into_iter()
+                .filter(|(encoding, extension)| is_valid_sibling_extension(*encoding, extension))
+                .map(|(encoding, extension)| {
+                    let extension = extension.strip_prefix('.').unwrap_or(&extension);
+                    (encoding, format!(".{}", extension))
+                })
+                .collect(),
+            max_open_streams: config.max_open_streams,
+            open_streams: Arc::new(AtomicUsize::new(0)),
+            cache: config.cache.map(FileCache::new),
+            access_log: None,
+            pre_serve: None,
+            transform: None,
+            html_meta_injector: None,
+            #[cfg(feature = "tokio")]
+            read_timeout: config.read_timeout,
+            vfs: Arc::new(StdFs),
+            #[cfg(feature = "tokio")]
+            async_vfs: Arc::new(TokioFs),
+        }
+    }
+
+    // create a static file server, checking up front that the configuration can be served
+    // and that the root directory exists and is a directory
+    pub fn try_from_config(config: StaticServerConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        let mut server = StaticServer::from_config(config);
+        server.check_root()?;
+        Ok(server)
+    }
+
+    // canonicalize the root directory through the current VFS and keep the result
+    pub(crate) fn check_root(&mut self) -> Result<(), ConfigError> {
+        let canonical = self
+            .canonical_root()
+            .map_err(|_| ConfigError::RootDirNotFound(self.root_dir.clone()))?;
+        if !self.is_dir(&canonical) {
+            return Err(ConfigError::RootDirNotADirectory(self.root_dir.clone()));
+        }
+        self.canonical_root = Some(canonical);
+        Ok(())
+    }
+
+    // the canonical root directory, resolved now unless it was at construction
+    fn canonical_root(&self) -> std::io::Result<PathBuf> {
+        match &self.canonical_root {
+            Some(root) => Ok(root.clone()),
+            None => self.vfs.canonicalize(&self.root_dir),
         }
     }
 
+    // start building a static file server for the given root directory
+    pub fn builder(root_dir: impl Into<PathBuf>) -> StaticServerBuilder {
+        StaticServerBuilder::new(root_dir)
+    }
+
     // resolve the file to be served, using the incoming request path
     pub fn resolve(&self, request_path: &str) -> Option<PathBuf> {
-        if !request_path.starts_with(&self.mount_path) {
-            return None;
+        self.resolve_checked(request_path)
+            .ok()
+            .map(|resolved| resolved.path)
+    }
+
+    // the canonical on-disk path read_file would serve for a request, index resolution
+    // included, without reading the body
+    pub fn canonical_path(&self, request_path: &str) -> Result<PathBuf, ServeError> {
+        self.resolve_checked(request_path)
+            .map(|resolved| resolved.path)
+    }
+
+    // resolve the file to be served, telling a request outside the mount path apart from
+    // one inside it that can't be served
+    pub fn resolve_detailed(&self, request_path: &str) -> Result<PathBuf, ResolveError> {
+        if !self.matches_mount(request_path) {
+            return Err(ResolveError::MountMismatch);
+        }
+
+        self.resolve_checked(request_path)
+            .map(|resolved| resolved.path)
+            .map_err(ResolveError::Serve)
+    }
+
+    // whether a request path falls under this server's mount path (or is an ACME challenge
+    // this server answers)
+    pub fn matches_mount(&self, request_path: &str) -> bool {
+        self.strip_mount(request_path).is_some()
+            || (self.acme_challenge_dir.is_some()
+                && request_path.starts_with(ACME_CHALLENGE_PREFIX))
+    }
+
+    // resolve the file to be served, reporting why resolution failed
+    fn resolve_checked(&self, request_path: &str) -> Result<Resolved, ServeError> {
+        // Reject overly long paths before doing any work on them
+        if self
+            .max_path_len
+            .is_some_and(|limit| request_path.len() > limit)
+        {
+            return Err(ServeError::InvalidPath);
+        }
+
+        let Some(negative) = &self.negative_cache else {
+            return self.resolve_within_root(request_path);
+        };
+        if negative.contains(request_path) {
+            return Err(ServeError::NotFound);
+        }
+        let outcome = self.resolve_within_root(request_path);
+        if matches!(outcome, Err(ServeError::NotFound)) {
+            negative.insert(request_path);
+        }
+        outcome
+    }
+
+    // map the request path onto an existing file inside the root directory
+    fn resolve_within_root(&self, request_path: &str) -> Result<Resolved, ServeError> {
+        // Drop a query string or fragment, such as a bundler's cache-busting `?v=3`
+        let request_path = strip_query(request_path);
+
+        // A request for exactly the mount path is answered as configured
+        if request_path == self.mount_path && self.mount_path != "/" {
+            match self.mount_root {
+                MountRootPolicy::ServeIndex => {}
+                MountRootPolicy::Redirect => {
+                    return Err(ServeError::Redirect {
+                        status: 301,
+                        location: format!("{}/", self.mount_path),
+                    });
+                }
+                MountRootPolicy::NotFound => return Err(ServeError::NotFound),
+            }
         }
 
         // Strip the mount path from the request path
-        let relative_path = request_path
-            .strip_prefix(&self.mount_path)
-            .unwrap_or("")
-            .trim_start_matches('/');
+        let relative_path = self.strip_mount(request_path).ok_or(ServeError::NotFound)?;
+
+        // Decode percent-escapes so encoded names match the files on disk
+        let relative_path = percent_decode_path(relative_path)?;
+        validate_components(&relative_path)?;
+
+        match self.resolve_candidates(&relative_path) {
+            // A client-side route of a single-page app loads the app itself
+            Err(ServeError::NotFound)
+                if self.spa_fallback && Path::new(&relative_path).extension().is_none() =>
+            {
+                self.resolve_relative("", IndexMode::Required)
+            }
+            outcome => outcome,
+        }
+    }
+
+    // resolve a decoded mount-relative path through plain resolution and clean URLs, or the
+    // try_files chain when one is configured
+    fn resolve_candidates(&self, relative_path: &str) -> Result<Resolved, ServeError> {
+        if self.try_files.is_empty() {
+            return match self.resolve_relative(relative_path, IndexMode::Auto) {
+                Err(ServeError::NotFound) => self.resolve_clean_url(relative_path),
+                outcome => outcome,
+            };
+        }
+
+        // Walk the try_files chain; the first candidate that resolves wins
+        for template in &self.try_files {
+            let candidate = template.replace("$uri", relative_path);
+            let mode = if candidate.ends_with('/') {
+                IndexMode::Required
+            } else {
+                IndexMode::Never
+            };
+
+            match self.resolve_relative(candidate.trim_start_matches('/'), mode) {
+                Err(ServeError::NotFound) => continue,
+                outcome => return outcome,
+            }
+        }
+
+        Err(ServeError::NotFound)
+    }
+
+    // retry a missed extension-less path with the clean URL extension appended; a real
+    // directory keeps its own (index) resolution
+    fn resolve_clean_url(&self, relative_path: &str) -> Result<Resolved, ServeError> {
+        let Some(extension) = &self.clean_url_extension else {
+            return Err(ServeError::NotFound);
+        };
+        let eligible = !relative_path.is_empty()
+            && !relative_path.ends_with('/')
+            && Path::new(relative_path).extension().is_none()
+            && !self.is_dir(&self.root_dir.join(relative_path));
+        if !eligible {
+            return Err(ServeError::NotFound);
+        }
+
+        self.resolve_relative(&format!("{relative_path}.{extension}"), IndexMode::Never)
+    }
+
+    // map a decoded mount-relative path onto an existing file inside the root directory
+    fn resolve_relative(
+        &self,
+        relative_path: &str,
+        index_mode: IndexMode,
+    ) -> Result<Resolved, ServeError> {
+        // Refuse dotfiles and dot-directories anywhere in the path
+        if !self.serve_hidden && has_hidden_component(Path::new(relative_path)) {
+            return Err(ServeError::Forbidden);
+        }
 
         // Join the relative path to the root directory
         let mut full_path = self.root_dir.join(relative_path);
+        let mut relative = relative_path.to_string();
+        let mut is_index = false;
+
+        let wants_index = match index_mode {
+            IndexMode::Auto => self.serve_index && self.is_dir(&full_path),
+            IndexMode::Required if !self.is_dir(&full_path) => return Err(ServeError::NotFound),
+            IndexMode::Required => true,
+            IndexMode::Never => false,
+        };
+
+        // If it's a directory and index serving applies, try to serve its index file
+        if wants_index
+            && !self.index_disabled_for(relative_path)
+            && let Some(index_name) = self.select_index(&full_path)
+        {
+            full_path = full_path.join(index_name);
+            relative = join_relative(relative_path, index_name);
+            is_index = true;
+        }
 
-        // If it's a directory and `serve_index` is true, try to serve index.html
-        if full_path.is_dir() && self.serve_index {
-            full_path = full_path.join("index.html");
+        // Refuse any symlink between the root and the file when symlinks aren't followed
+        if !self.follow_symlinks && self.has_symlink_component(&full_path) {
+            return Err(ServeError::NotFound);
         }
 
-        let canonical_full = canonicalize(&full_path).ok()?;
-        let canonical_root = canonicalize(&self.root_dir).ok()?;
+        // A permission problem on the way to the file is reported, anything else is a miss
+        let canonical_full = self
+            .canonicalize_cached(&full_path)
+            .map_err(missing_unless_denied)?;
+        let canonical_root = self.canonical_root().map_err(|_| ServeError::NotFound)?;
 
         if !canonical_full.starts_with(&canonical_root) {
-            return None;
+            return Err(ServeError::NotFound);
+        }
+
+        // With tenant jails, the target must also stay inside the requested tenant's directory
+        if self.per_tenant_jail
+            && let Some(tenant) = tenant_of(&relative)?
+        {
+            let jail = self
+                .vfs
+                .canonicalize(&self.root_dir.join(tenant))
+                .map_err(|_| ServeError::NotFound)?;
+            if !canonical_full.starts_with(&jail) {
+                return Err(ServeError::NotFound);
+            }
+        }
+
+        // A followed symlink must not lead to a hidden target either
+        if !self.serve_hidden
+            && canonical_full
+                .strip_prefix(&canonical_root)
+                .map_or(true, has_hidden_component)
+        {
+            return Err(ServeError::Forbidden);
         }
 
         // Only return it if the file exists and is not a directory
-        if canonical_full.exists() && canonical_full.is_file() {
-            Some(canonical_full)
-        } else {
-            None
+        match self.vfs.metadata(&canonical_full) {
+            Ok(metadata)
+                if metadata.is_file()
+                    && !(self.extension_allowed(Path::new(&relative))
+                        && self.extension_allowed(&canonical_full)) =>
+            {
+                Err(ServeError::NotFound)
+            }
+            Ok(metadata) if metadata.is_file() => Ok(Resolved {
+                path: canonical_full,
+                relative,
+                is_index,
+            }),
+            Ok(metadata) if metadata.is_dir() && matches!(index_mode, IndexMode::Auto) => {
+                Err(self.bare_directory_error())
+            }
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => Err(ServeError::Forbidden),
+            _ => Err(ServeError::NotFound),
+        }
+    }
+
+    // whether a file's extension passes allowed_extensions, or denied_extensions when no
+    // allowlist is set; a file without an extension counts as having the empty one. While
+    // lone `.gz` files are served, one is judged by the name of the file it stands for
+    fn extension_allowed(&self, path: &Path) -> bool {
+        let path = match (self.serve_lone_gzip, self.sibling_suffix(Encoding::Gzip)) {
+            (true, Some(suffix)) => path
+                .to_str()
+                .and_then(|name| name.strip_suffix(suffix))
+                .map_or(path, Path::new),
+            _ => path,
+        };
+        let extension = path.extension().map_or(String::new(), |extension| {
+            extension.to_string_lossy().to_ascii_lowercase()
+        });
+        match &self.allowed_extensions {
+            Some(allowed) => allowed.contains(&extension),
+            None => !self.denied_extensions.contains(&extension),
         }
     }
 
+    // set a sink which receives an access log record after every request
+    pub fn set_access_log(&mut self, sink: AccessLogSink) {
+        self.access_log = Some(sink);
+    }
+
+    // set a hook which can allow, deny or replace each request after resolution; it guards
+    // the read_file family and byte ranges (a replaced file is only served whole), and files
+    // it denies or replaces are left out of archives, listings and SRI hashes; open_file and
+    // read_file_stream hand out raw handles and are left to the caller to gate
+    pub fn set_pre_serve(&mut self, hook: PreServeHook) {
+        self.pre_serve = Some(hook);
+    }
+
+    // set a transform applied to every served body; while one is set, ETags are weak and
+    // hash the transformed bytes, since neither the build ID nor the file's metadata change
+    // when the transform does
+    pub fn set_transform(&mut self, transform: TransformHook) {
+        self.transform = Some(transform);
+    }
+
+    // set a source of meta tags for HTML bodies; the tags go right after the opening <head>
+    // tag (after an injected <base href>, before the transform runs), and a document without
+    // a <head> is served as is. Injection isn't idempotent, so a page which already carries
+    // the tags gets them twice. ETags are weak content hashes, as with a transform
+    pub fn set_html_meta_injector(&mut self, injector: HtmlMetaInjector) {
+        self.html_meta_injector = Some(injector);
+    }
+
     // read the file from disk
     pub fn read_file(&self, request_path: &str) -> Result<StaticFile, ServeError> {
-        let file_path = self.resolve(request_path).ok_or(ServeError::NotFound)?;
+        self.serve(request_path, None, &RequestContext::default())
+    }
 
-        let body = std::fs::read(&file_path).map_err(ServeError::Io)?;
+    // read the file unless the Referer header names a host outside allowed_referers, which
+    // is answered as Forbidden (or the 403 error page) to stop other sites hotlinking
+    pub fn read_file_with_referer(
+        &self,
+        request_path: &str,
+        referer: Option<&str>,
+    ) -> Result<StaticFile, ServeError> {
+        if !self.referer_allowed(referer) {
+            let result = self.error_page_for(ServeError::Forbidden);
+            self.log_access(request_path, &result);
+            return result;
+        }
 
-        let mime_type = guess_mime_type(file_path.as_path());
+        self.read_file(request_path)
+    }
 
-        Ok(StaticFile {
-            body,
-            mime_type,
-            path: file_path,
-        })
+    // whether a Referer header value passes the hotlink check
+    fn referer_allowed(&self, referer: Option<&str>) -> bool {
+        let Some(allowed) = &self.allowed_referers else {
+            return true;
+        };
+        let Some(referer) = referer.map(str::trim).filter(|referer| !referer.is_empty()) else {
+            return self.allow_missing_referer;
+        };
+        let Some(host) = referer_host(referer) else {
+            return false;
+        };
+
+        allowed
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.is_empty() || sub.ends_with('.')),
+                None => host == *pattern,
+            })
     }
 
-    // utility to return the mount path
-    pub fn mount_path(&self) -> &str {
-        &self.mount_path
+    // the URL to redirect to when a request arrived on a host other than the canonical one,
+    // e.g. `www.example.com` to `example.com`; the port and letter case of the Host header
+    // are ignored, and the request path (with its query) is kept. A canonical host without a
+    // scheme is reached over https
+    pub fn host_redirect(&self, host: &str, request_path: &str) -> Option<String> {
+        let canonical = self.canonical_host.as_deref()?;
+        let (origin, authority) = match canonical.split_once("://") {
+            Some((_, authority)) => (canonical.trim_end_matches('/').to_string(), authority),
+            None => (
+                format!("https://{}", canonical.trim_end_matches('/')),
+                canonical,
+            ),
+        };
+        if authority_host(host.trim()) == authority_host(authority) {
+            return None;
+        }
+
+        let path = if request_path.starts_with('/') {
+            request_path.to_string()
+        } else {
+            format!("/{}", request_path)
+        };
+        Some(format!("{}{}", origin, path))
     }
 
-    // utility to return the root dir
-    pub fn root_dir(&self) -> &Path {
-        &self.root_dir
+    // read the file named by a route capture such as the `rest` of `/static/*rest`, which is
+    // already relative to the mount path; it is re-anchored under the mount so decoding,
+    // traversal checks, error pages and logging all apply as for a full request path. The
+    // capture is expected in its raw, percent-encoded form
+    pub fn read_captured(&self, captured: &str) -> Result<StaticFile, ServeError> {
+        let request_path = format!(
+            "{}/{}",
+            self.mount_path.trim_end_matches('/'),
+            captured.trim_start_matches('/')
+        );
+        self.read_file(&request_path)
     }
 
-    // utility to return whether serving index.html is true or false
-    pub fn serve_index(&self) -> bool {
-        self.serve_index
+    // read the file from disk, handing the request context to the pre-serve hook
+    pub fn read_file_with_context(
+        &self,
+        request_path: &str,
+        context: &RequestContext,
+    ) -> Result<StaticFile, ServeError> {
+        self.serve(request_path, None, context)
     }
-}
 
-// helper function to guess the mime type
-pub fn guess_mime_type(path: &Path) -> Cow<'static, str> {
-    Cow::Owned(
-        mime_guess::from_path(path)
-            .first_or_octet_stream()
-            .to_string(),
-    )
-}
+    // read the first of several request paths that exists, e.g. `logo.svg` and then
+    // `logo.png`; each path is resolved on its own with the usual mount stripping and checks,
+    // only a miss moves on to the next one, and the error page stands in once all of them
+    // miss. The access log records the path which decided the outcome
+    pub fn read_first_of(&self, request_paths: &[&str]) -> Result<StaticFile, ServeError> {
+        let mut outcome = (request_paths.first().copied().unwrap_or(""), None);
+        for request_path in request_paths {
+            match self.read_file_encoded(request_path, None, &RequestContext::default()) {
+                Err(ServeError::NotFound) => outcome = (request_path, None),
+                result => {
+                    outcome = (request_path, Some(result));
+                    break;
+                }
+            }
+        }
 
-// helper function to normalize the mount path of the StaticServer
-fn normalize_mount_path(path: &str) -> String {
-    if path == "/" {
-        return "/".to_string();
+        let (request_path, result) = outcome;
+        let result = result
+            .unwrap_or(Err(ServeError::NotFound))
+            .or_else(|err| self.error_page_for(err));
+        self.log_access(request_path, &result);
+        result
     }
 
-    let trimmed = path.trim_end_matches('/');
+    // read the file in the representation the client hints ask for; with `save_data` set, the
+    // smallest encoding the client accepts is served (precompressed siblings by their size on
+    // disk, then compression at read time) rather than the one it weighs highest. The hints
+    // also reach the pre-serve hook as Accept-Encoding and Save-Data headers
+    pub fn read_file_with_hints(
+        &self,
+        request_path: &str,
+        hints: &ClientHints,
+    ) -> Result<StaticFile, ServeError> {
+        self.serve(
+            request_path,
+            hints.accept_encoding.as_deref(),
+            &hints.to_context(),
+        )
+    }
 
-    if trimmed.starts_with('/') {
-        trimmed.to_string()
-    } else {
-        format!("/{}", trimmed)
+    // answer a request: ACME challenges first, then the file itself with the error page as
+    // the fallback, and finally the access log entry
+    fn serve(
+        &self,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+        context: &RequestContext,
+    ) -> Result<StaticFile, ServeError> {
+        let result = match self.acme_challenge(request_path) {
+            Some(challenge) => challenge,
+            None => self
+                .read_file_encoded(request_path, accept_encoding, context)
+                .or_else(|err| self.negotiated_error_page(err, context)),
+        };
+        self.log_access(request_path, &result);
+        result
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::{self, File};
-    use std::io::Write;
-    use tempfile::tempdir;
+    // serve an ACME HTTP-01 challenge token from the dedicated challenge directory; None when
+    // the request isn't for a challenge (or no challenge directory is configured). The main
+    // root, the mount path and the dotfile policy don't apply here
+    fn acme_challenge(&self, request_path: &str) -> Option<Result<StaticFile, ServeError>> {
+        let challenge_dir = self.acme_challenge_dir.as_ref()?;
+        let token = request_path.strip_prefix(ACME_CHALLENGE_PREFIX)?;
 
-    #[test]
-    fn serves_existing_file() {
-        let dir = tempdir().unwrap();
-        let file_path = dir.path().join("hello.txt");
+        // tokens are base64url, so anything else (separators, dots, escapes) can't be one
+        let valid_token = !token.is_empty()
+            && token
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid_token {
+            return Some(Err(ServeError::NotFound));
+        }
 
-        let mut file = File::create(&file_path).unwrap();
-        write!(file, "Hello, world!").unwrap();
+        let path = challenge_dir.join(token);
+        let is_file = self
+            .vfs
+            .metadata(&path)
+            .is_ok_and(|metadata| metadata.is_file());
+        if !is_file {
+            return Some(Err(ServeError::NotFound));
+        }
 
-        let config = StaticServerConfig {
-            mount_path: "/static".into(),
-            root_dir: dir.path().to_path_buf(),
-            serve_index: false,
+        Some(
+            self.vfs
+                .read(&path, self.open_nofollow)
+                .map_err(ServeError::from_io)
+                .map(|body| StaticFile {
+                    len: body.len() as u64,
+                    body,
+                    mime_type: Cow::Borrowed("text/plain"),
+                    path,
+                    headers: vec![("Cache-Control", "no-store".to_string())],
+                    status: 200,
+                    etag: None,
+                    modified: None,
+                    content_encoding: None,
+                    source: FileSource::Disk,
+                }),
+        )
+    }
+
+    // swap an error for the configured error page, unless the page is HTML and the client's
+    // Accept header prefers JSON; the plain error is returned then so the caller can answer
+    // with a JSON body of its own
+    fn negotiated_error_page(
+        &self,
+        err: ServeError,
+        context: &RequestContext,
+    ) -> Result<StaticFile, ServeError> {
+        let wants_json = context.header("Accept").is_some_and(prefers_json);
+        match self.error_response(err.status_code()) {
+            Some(page) if !(wants_json && page.mime_type.starts_with("text/html")) => Ok(page),
+            _ => Err(err),
+        }
+    }
+
+    // swap an error for the configured error page, if there is one
+    fn error_page_for(&self, err: ServeError) -> Result<StaticFile, ServeError> {
+        self.error_response(err.status_code()).ok_or(err)
+    }
+
+    // the error page mapped to a status, served with that status; None when no page is
+    // mapped (or it can't be read), so the caller falls back to the plain error
+    pub fn error_response(&self, status: u16) -> Option<StaticFile> {
+        let page = match self.error_pages.get(&status) {
+            Some(page) => page.as_path(),
+            None if status == 403 => self.forbidden_page.as_deref()?,
+            None if status == 404 => self.not_found_file.as_deref()?,
+            None => return None,
         };
 
-        let server = StaticServer::from_config(config);
-        let result = server.read_file("/static/hello.txt");
+        self.read_error_page(page, status)
+    }
 
-        assert!(result.is_ok(), "Expected file to be served successfully");
+    // read an error page, relative to the root directory, to be served with the given status;
+    // a missing or escaping page yields None so the plain error is returned instead
+    fn read_error_page(&self, page: &Path, status: u16) -> Option<StaticFile> {
+        let canonical_root = self.canonical_root().ok()?;
+        let page_path = self.vfs.canonicalize(&self.root_dir.join(page)).ok()?;
+        if !page_path.starts_with(&canonical_root) {
+            return None;
+        }
 
-        let static_file = result.unwrap();
-        assert_eq!(static_file.mime_type, "text/plain");
-        assert_eq!(static_file.body, b"Hello, world!");
+        let body = self.vfs.read(&page_path, self.open_nofollow).ok()?;
+        let relative = page_path
+            .strip_prefix(&canonical_root)
+            .ok()?
+            .to_string_lossy();
+
+        Some(StaticFile {
+            len: body.len() as u64,
+            body,
+            mime_type: self.mime_with_default_charset(&relative),
+            headers: self.response_headers(),
+            path: page_path,
+            status,
+            etag: None,
+            modified: None,
+            content_encoding: None,
+            source: FileSource::Disk,
+        })
     }
 
-    #[test]
-    fn returns_not_found_for_missing_file() {
-        let dir = tempdir().unwrap();
+    // read the file from disk, reporting a request outside the mount path as a mismatch
+    // rather than a miss, so routers can move on to the next mount
+    pub fn read_file_detailed(&self, request_path: &str) -> Result<StaticFile, ResolveError> {
+        if !self.matches_mount(request_path) {
+            return Err(ResolveError::MountMismatch);
+        }
 
-        let config = StaticServerConfig {
-            mount_path: "/static".into(),
-            root_dir: dir.path().to_path_buf(),
-            serve_index: false,
+        self.read_file(request_path).map_err(ResolveError::Serve)
+    }
+
+    // read the file from disk, choosing the encoding from the client's Accept-Encoding value;
+    // with compression enabled a gzip body is produced for clients which accept it, and
+    // content_encoding reports the coding to send
+    pub fn read_file_negotiated(
+        &self,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+    ) -> Result<StaticFile, ServeError> {
+        self.serve(request_path, accept_encoding, &RequestContext::default())
+    }
+
+    // read the file in the negotiated encoding, without writing to the access log
+    fn read_file_encoded(
+        &self,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+        context: &RequestContext,
+    ) -> Result<StaticFile, ServeError> {
+        let resolved = self.resolve_checked(request_path);
+        if self.directory_listing
+            && matches!(resolved, Err(ServeError::NotFound | ServeError::Forbidden))
+            && let Ok(listing) = self.list_directory(request_path)
+        {
+            return Ok(listing);
+        }
+        let resolved = match resolved {
+            Err(ServeError::NotFound) => {
+                return self
+                    .read_gzip_only(request_path, accept_encoding, context)
+                    .unwrap_or(Err(ServeError::NotFound));
+            }
+            outcome => outcome?,
         };
+        if let Some(file) = self.check_pre_serve(&resolved, context)? {
+            return Ok(file);
+        }
 
-        let server = StaticServer::from_config(config);
-        let result = server.read_file("/static/missing.txt");
+        let metadata = self
+            .vfs
+            .metadata(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        self.check_file_size(&metadata)?;
+        let mime_type = self.mime_for_resolved(&resolved);
 
-        assert!(matches!(result, Err(ServeError::NotFound)));
-    }
+        let offered = self.offered_encodings(&resolved, &metadata, &mime_type);
+        let available: Vec<Encoding> = offered.iter().map(|(encoding, _)| *encoding).collect();
+        let encoding = if context.header("Save-Data").is_some_and(is_save_data_on) {
+            let sized: Vec<(Encoding, Option<u64>)> = offered
+                .iter()
+                .map(|(encoding, sibling)| {
+                    let size = match sibling {
+                        Some(sibling) => self.vfs.metadata(sibling).ok().map(|meta| meta.len()),
+                        None if *encoding == Encoding::Identity => Some(metadata.len()),
+                        None => None,
+                    };
+                    (*encoding, size)
+                })
+                .collect();
+            negotiate_smallest(accept_encoding, &sized)
+        } else {
+            negotiate_encoding(accept_encoding, &available)
+        };
 
-    #[test]
-    fn serves_index_file_when_directory_requested() {
-        let dir = tempdir().unwrap();
-        let subdir = dir.path().join("docs");
-        fs::create_dir(&subdir).unwrap();
+        let precompressed = offered
+            .into_iter()
+            .find(|(offered, _)| *offered == encoding)
+            .and_then(|(_, sibling)| sibling);
+        let (body, source) = match precompressed {
+            Some(sibling) => {
+                let sibling_metadata = self.vfs.metadata(&sibling).map_err(ServeError::from_io)?;
+                self.cached_body(&sibling, encoding, false, &sibling_metadata, || {
+                    self.vfs
+                        .read(&sibling, self.open_nofollow)
+                        .map_err(ServeError::from_io)
+                })?
+            }
+            None if encoding == Encoding::Gzip => {
+                let as_index = self.injects_base_href(&resolved, &mime_type);
+                self.cached_body(&resolved.path, encoding, as_index, &metadata, || {
+                    let (identity, _) = self.identity_body(&resolved, &metadata, &mime_type)?;
+                    Ok(gzip_encode(&identity))
+                })?
+            }
+            None => self.identity_body(&resolved, &metadata, &mime_type)?,
+        };
 
-        let index_path = subdir.join("index.html");
-        let mut index_file = File::create(&index_path).unwrap();
-        write!(index_file, "<h1>Index</h1>").unwrap();
+        let headers = self.body_headers(&resolved, &mime_type, available.len() > 1, &body);
 
-        let config = StaticServerConfig {
-            mount_path: "/static".into(),
-            root_dir: dir.path().to_path_buf(),
-            serve_index: true,
+        // the validator always describes the identity file, with a marker naming the coding
+        // so a shared cache never confuses one representation for another
+        let identity = (encoding == Encoding::Identity).then_some(body.as_slice());
+        let etag = encoded_etag(
+            &self.served_etag(&resolved, &metadata, &mime_type, identity)?,
+            encoding,
+        );
+
+        Ok(StaticFile {
+            len: body.len() as u64,
+            body,
+            mime_type,
+            etag: Some(etag),
+            modified: self.modified_time(&metadata),
+            path: resolved.path,
+            headers,
+            status: 200,
+            content_encoding: (encoding != Encoding::Identity)
+                .then(|| Cow::Borrowed(encoding.as_str())),
+            source,
+        })
+    }
+
+    // the suffix of precompressed siblings in a coding, as configured by
+    // precompressed_extensions
+    fn sibling_suffix(&self, encoding: Encoding) -> Option<&str> {
+        self.precompressed_extensions
+            .get(&encoding)
+            .map(String::as_str)
+    }
+
+    // serve a missed file from a lone `.gz` beside where it would be, when serve_lone_gzip is
+    // on: as is to clients which accept gzip, decompressed (within the decompression limit)
+    // to the rest; None when there is no such file either
+    fn read_gzip_only(
+        &self,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+        context: &RequestContext,
+    ) -> Option<Result<StaticFile, ServeError>> {
+        let request_path = strip_query(request_path);
+        if !self.serve_lone_gzip || request_path.ends_with('/') {
+            return None;
+        }
+        let suffix = self.sibling_suffix(Encoding::Gzip)?;
+        let compressed = self
+            .resolve_checked(&format!("{}{}", request_path, suffix))
+            .ok()?;
+        let relative = compressed.relative.strip_suffix(suffix)?.to_string();
+        let resolved = Resolved {
+            relative,
+            is_index: false,
+            ..compressed
         };
 
-        let server = StaticServer::from_config(config);
-        let result = server.read_file("/static/docs");
+        Some(self.read_decompressible(resolved, accept_encoding, context))
+    }
 
-        assert!(result.is_ok(), "Expected index.html to be served");
+    // read a gzip file standing in for the identity file named by `resolved.relative`
+    fn read_decompressible(
+        &self,
+        resolved: Resolved,
+        accept_encoding: Option<&str>,
+        context: &RequestContext,
+    ) -> Result<StaticFile, ServeError> {
+        if let Some(file) = self.check_pre_serve(&resolved, context)? {
+            return Ok(file);
+        }
 
-        let static_file = result.unwrap();
-        assert_eq!(static_file.mime_type, "text/html");
-        assert_eq!(static_file.body, b"<h1>Index</h1>");
+        let metadata = self
+            .vfs
+            .metadata(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        self.check_file_size(&metadata)?;
+        let mime_type = self.mime_with_default_charset(&resolved.relative);
+        let compressed = self
+            .vfs
+            .read(&resolved.path, self.open_nofollow)
+            .map_err(ServeError::from_io)?;
+
+        let encoding = negotiate_encoding(accept_encoding, &[Encoding::Identity, Encoding::Gzip]);
+        // the body is only decoded when the client can't take it compressed or it is rewritten,
+        // and a rewritten body going to a gzip client is compressed again
+        let (body, identity) =
+            if encoding == Encoding::Gzip && !self.rewrites_body(&resolved, &mime_type) {
+                (compressed, None)
+            } else {
+                let limit = self.decompression_limit();
+                let decoded =
+                    gzip_decode(&compressed, Some(limit)).map_err(|err| match err.kind() {
+                        ErrorKind::FileTooLarge => ServeError::TooLarge {
+                            size: gzip_declared_size(&compressed).unwrap_or(limit),
+                            limit,
+                        },
+                        _ => ServeError::Io(err),
+                    })?;
+                let identity = self.finish_body(&resolved, &mime_type, decoded);
+                match encoding {
+                    Encoding::Gzip => (gzip_encode(&identity), Some(identity)),
+                    _ => (identity, None),
+                }
+            };
+        let etag = self.served_etag(
+            &resolved,
+            &metadata,
+            &mime_type,
+            Some(identity.as_deref().unwrap_or(&body)),
+        )?;
+
+        let headers = self.body_headers(&resolved, &mime_type, true, &body);
+
+        Ok(StaticFile {
+            len: body.len() as u64,
+            body,
+            mime_type,
+            etag: Some(encoded_etag(&etag, encoding)),
+            modified: self.modified_time(&metadata),
+            path: resolved.path,
+            headers,
+            status: 200,
+            content_encoding: (encoding != Encoding::Identity)
+                .then(|| Cow::Borrowed(encoding.as_str())),
+            source: FileSource::Disk,
+        })
     }
 
-    #[test]
-    fn directory_without_index_returns_not_found() {
-        let dir = tempdir().unwrap();
-        let subdir = dir.path().join("images");
-        fs::create_dir(&subdir).unwrap();
+    // the most a lone `.gz` file may decompress to: max_decompressed_size, else max_file_size,
+    // else DEFAULT_MAX_DECOMPRESSED_SIZE
+    fn decompression_limit(&self) -> u64 {
+        self.max_decompressed_size
+            .or(self.max_file_size)
+            .unwrap_or(DEFAULT_MAX_DECOMPRESSED_SIZE)
+    }
 
-        let config = StaticServerConfig {
-            mount_path: "/static".into(),
-            root_dir: dir.path().to_path_buf(),
-            serve_index: true,
+    // refuse files over max_file_size before any of their bytes are read
+    fn check_file_size(&self, metadata: &Metadata) -> Result<(), ServeError> {
+        match self.max_file_size {
+            Some(limit) if metadata.len() > limit => Err(ServeError::TooLarge {
+                size: metadata.len(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // the encodings the server could send for a request, to help diagnose negotiation:
+    // identity always, gzip when a `.gz` sibling exists or the file is compressed at read
+    // time, and brotli when a `.br` sibling exists; empty when the request doesn't resolve
+    pub fn available_encodings(&self, request_path: &str) -> Vec<Encoding> {
+        let Ok(resolved) = self.resolve_checked(request_path) else {
+            return Vec::new();
+        };
+        let Ok(metadata) = self.vfs.metadata(&resolved.path) else {
+            return Vec::new();
         };
 
-        let server = StaticServer::from_config(config);
-        let result = server.read_file("/static/images");
+        let mime_type = self.mime_for_resolved(&resolved);
+        self.offered_encodings(&resolved, &metadata, &mime_type)
+            .into_iter()
+            .map(|(encoding, _)| encoding)
+            .collect()
+    }
 
-        assert!(matches!(result, Err(ServeError::NotFound)));
+    // the encodings on offer for a file, each with the precompressed sibling that supplies
+    // it (None for identity and read-time gzip). Siblings hold the bytes on disk, so they
+    // can't stand in for a body that is rewritten before it is sent; read-time gzip needs
+    // a compressible type of at least compress_min_size bytes
+    fn offered_encodings(
+        &self,
+        resolved: &Resolved,
+        metadata: &Metadata,
+        mime_type: &str,
+    ) -> Vec<(Encoding, Option<PathBuf>)> {
+        let rewritten = self.rewrites_body(resolved, mime_type);
+        let sibling = |encoding| {
+            (!rewritten)
+                .then(|| self.precompressed_sibling(&resolved.path, encoding))
+                .flatten()
+        };
+
+        let mut offered = vec![(Encoding::Identity, None)];
+        match sibling(Encoding::Gzip) {
+            Some(gzip) => offered.push((Encoding::Gzip, Some(gzip))),
+            None if self.compress
+                && is_compressible(mime_type)
+                && metadata.len() >= self.compress_min_size as u64 =>
+            {
+                offered.push((Encoding::Gzip, None))
+            }
+            None => {}
+        }
+        if let Some(brotli) = sibling(Encoding::Brotli) {
+            offered.push((Encoding::Brotli, Some(brotli)));
+        }
+        offered
+    }
+
+    // the precompressed sibling of a file in the given coding, when one exists and is at
+    // least as new as the file, so a stale `.gz` left behind by an old build is ignored; a
+    // symlinked sibling is never used, since it could point anywhere
+    fn precompressed_sibling(&self, path: &Path, encoding: Encoding) -> Option<PathBuf> {
+        let mut sibling = path.as_os_str().to_owned();
+        sibling.push(self.sibling_suffix(encoding)?);
+        let sibling = PathBuf::from(sibling);
+
+        let original = self.vfs.metadata(path).ok()?.modified().ok();
+        let metadata = self.vfs.symlink_metadata(&sibling).ok()?;
+        let fresh = metadata.is_file() && metadata.modified().ok() >= original;
+        fresh.then_some(sibling)
+    }
+
+    // headers attached to a served body: the file headers, Vary when another encoding could
+    // have been chosen, and the digest of the bytes sent
+    fn body_headers(
+        &self,
+        resolved: &Resolved,
+        mime_type: &str,
+        varies: bool,
+        body: &[u8],
+    ) -> Vec<(&'static str, String)> {
+        let mut headers = self.file_headers(resolved, mime_type);
+        if varies {
+            headers.push(("Vary", "Accept-Encoding".to_string()));
+        }
+        if let Some(algorithm) = self.content_digest {
+            headers.push(("Content-Digest", content_digest(algorithm, body)));
+        }
+        headers
+    }
+
+    // headers attached to a served file: the server-wide ones plus those that depend on
+    // which file was resolved
+    fn file_headers(&self, resolved: &Resolved, mime_type: &str) -> Vec<(&'static str, String)> {
+        let mut headers = self.response_headers();
+        if self.immutable_for_hashed
+            && resolved
+                .path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(has_content_hash)
+        {
+            headers.retain(|(name, _)| *name != "Cache-Control");
+            headers.push(("Cache-Control", IMMUTABLE_CACHE_CONTROL.to_string()));
+        }
+        if let Some(worker) = &self.service_worker
+            && worker.path == resolved.relative
+        {
+            // browsers must revalidate the script to pick up new worker versions
+            headers.retain(|(name, _)| *name != "Cache-Control");
+            headers.push(("Cache-Control", "no-cache".to_string()));
+            headers.push(("Service-Worker-Allowed", worker.scope.clone()));
+        }
+        if self.content_location && resolved.is_index {
+            headers.push((
+                "Content-Location",
+                format!("{}{}", self.base_href(), resolved.relative),
+            ));
+        }
+        if !self.preconnect_origins.is_empty() && mime_type.starts_with("text/html") {
+            let links: Vec<String> = self
+                .preconnect_origins
+                .iter()
+                .map(|origin| format!("<{}>; rel=preconnect", origin))
+                .collect();
+            headers.push(("Link", links.join(", ")));
+        }
+        headers
+    }
+
+    // run the pre-serve hook, if one is set; Some carries a replacement file
+    fn check_pre_serve(
+        &self,
+        resolved: &Resolved,
+        context: &RequestContext,
+    ) -> Result<Option<StaticFile>, ServeError> {
+        let Some(hook) = &self.pre_serve else {
+            return Ok(None);
+        };
+
+        match hook(&resolved.path, context) {
+            PreServeDecision::Allow => Ok(None),
+            PreServeDecision::Deny(status) => Err(ServeError::Denied { status }),
+            PreServeDecision::Replace(file) => Ok(Some(file)),
+        }
+    }
+
+    // whether the pre-serve hook lets a file through untouched, judged without request
+    // headers; archives, listings and SRI hashes leave out files it denies or replaces
+    fn pre_serve_allows(&self, resolved: &Resolved) -> bool {
+        matches!(
+            self.check_pre_serve(resolved, &RequestContext::default()),
+            Ok(None)
+        )
+    }
+
+    // check a request method before serving; only GET and HEAD make sense for static files,
+    // so everything else (TRACE and CONNECT included) is refused
+    pub fn check_method(&self, method: &str) -> Result<(), ServeError> {
+        match method.to_ascii_uppercase().as_str() {
+            "GET" | "HEAD" => Ok(()),
+            _ => Err(ServeError::MethodNotAllowed {
+                method: method.to_string(),
+            }),
+        }
+    }
+
+    // answer a HEAD request: the same status, MIME type, length and headers a GET would get,
+    // with an empty body; the file itself is only read when its body is rewritten (base href
+    // injection or a transform), since the length isn't known otherwise, or when a
+    // Content-Digest of it is sent
+    pub fn read_file_head(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        let result = match self.acme_challenge(request_path) {
+            Some(challenge) => challenge,
+            None => self
+                .read_head_unlogged(request_path)
+                .or_else(|err| self.error_page_for(err)),
+        }
+        .map(|mut file| {
+            file.body = Vec::new();
+            file
+        });
+        self.log_access(request_path, &result);
+        result
+    }
+
+    // the HEAD response for a file, without writing to the access log
+    fn read_head_unlogged(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        let resolved = self.resolve_checked(request_path)?;
+        if let Some(file) = self.check_pre_serve(&resolved, &RequestContext::default())? {
+            return Ok(file);
+        }
+
+        let metadata = self
+            .vfs
+            .metadata(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        self.check_file_size(&metadata)?;
+        let mime_type = self.mime_for_resolved(&resolved);
+
+        let varies = self
+            .offered_encodings(&resolved, &metadata, &mime_type)
+            .len()
+            > 1;
+        let (len, etag, headers) =
+            if self.rewrites_body(&resolved, &mime_type) || self.content_digest.is_some() {
+                let (body, _) = self.identity_body(&resolved, &metadata, &mime_type)?;
+                let etag = self.served_etag(&resolved, &metadata, &mime_type, Some(&body))?;
+                let headers = self.body_headers(&resolved, &mime_type, varies, &body);
+                (body.len() as u64, etag, headers)
+            } else {
+                let etag = self.served_etag(&resolved, &metadata, &mime_type, None)?;
+                let mut headers = self.file_headers(&resolved, &mime_type);
+                if varies {
+                    headers.push(("Vary", "Accept-Encoding".to_string()));
+                }
+                (metadata.len(), etag, headers)
+            };
+
+        Ok(StaticFile {
+            body: Vec::new(),
+            len,
+            headers,
+            mime_type,
+            path: resolved.path,
+            status: 200,
+            etag: Some(etag),
+            modified: self.modified_time(&metadata),
+            content_encoding: None,
+            source: FileSource::Disk,
+        })
+    }
+
+    // look up a file's size, MIME type and modification time without opening it, for HEAD
+    // handlers which only need the headers; resolution and its checks are those of resolve,
+    // and the size is that of the file on disk, before any body rewriting
+    pub fn stat(&self, request_path: &str) -> Result<StaticFileMeta, ServeError> {
+        let resolved = self.resolve_checked(request_path)?;
+        let metadata = self
+            .vfs
+            .metadata(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        Ok(StaticFileMeta {
+            size: metadata.len(),
+            mime_type: self.mime_for_resolved(&resolved),
+            modified: self.modified_time(&metadata),
+        })
+    }
+
+    // the identity body of a resolved file, with any HTML rewriting applied
+    fn identity_body(
+        &self,
+        resolved: &Resolved,
+        metadata: &Metadata,
+        mime_type: &str,
+    ) -> Result<(Vec<u8>, FileSource), ServeError> {
+        let as_index = self.injects_base_href(resolved, mime_type);
+        self.cached_body(
+            &resolved.path,
+            Encoding::Identity,
+            as_index,
+            metadata,
+            || {
+                let body = self
+                    .vfs
+                    .read(&resolved.path, self.open_nofollow)
+                    .map_err(ServeError::from_io)?;
+                Ok(self.finish_body(resolved, mime_type, body))
+            },
+        )
+    }
+
+    // apply the body rewrites (base href injection, meta tags, then the transform) to the
+    // bytes read from disk
+    fn finish_body(&self, resolved: &Resolved, mime_type: &str, mut body: Vec<u8>) -> Vec<u8> {
+        // Point relative URLs in an SPA index at the mount path
+        if self.injects_base_href(resolved, mime_type)
+            && let Some(injected) = html::inject_base_href(&body, &self.base_href())
+        {
+            body = injected;
+        }
+
+        if let Some(injector) = &self.html_meta_injector
+            && mime_type.starts_with("text/html")
+        {
+            let pairs = injector(&resolved.path);
+            if !pairs.is_empty()
+                && let Some(injected) = html::insert_into_head(&body, &html::meta_tags(&pairs))
+            {
+                body = injected;
+            }
+        }
+
+        if let Some(transform) = &self.transform {
+            body = transform(&resolved.path, mime_type, body);
+        }
+
+        body
+    }
+
+    // set the filesystem read_file_async reads bodies from
+    #[cfg(feature = "tokio")]
+    pub fn set_async_vfs(&mut self, vfs: Arc<dyn AsyncVfs>) {
+        self.async_vfs = vfs;
+    }
+
+    // read the file with an asynchronous body read, giving up with ServeError::Timeout once
+    // read_timeout elapses; resolution uses the synchronous Vfs, and the identity body is
+    // served (the cache is consulted first, so hits never wait on the filesystem)
+    #[cfg(feature = "tokio")]
+    pub async fn read_file_async(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        let result = match self.acme_challenge(request_path) {
+            Some(challenge) => challenge,
+            None => match self.read_async_unlogged(request_path).await {
+                Ok(file) => Ok(file),
+                Err(err) => self.error_page_for(err),
+            },
+        };
+        self.log_access(request_path, &result);
+        result
+    }
+
+    // the asynchronous read, without writing to the access log
+    #[cfg(feature = "tokio")]
+    async fn read_async_unlogged(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        let resolved = self.resolve_checked(request_path)?;
+        if let Some(file) = self.check_pre_serve(&resolved, &RequestContext::default())? {
+            return Ok(file);
+        }
+
+        let metadata = self
+            .vfs
+            .metadata(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        self.check_file_size(&metadata)?;
+        let mime_type = self.mime_for_resolved(&resolved);
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|cache| cache.admits(metadata.len()));
+
+        let as_index = self.injects_base_href(&resolved, &mime_type);
+        let cached = cache
+            .and_then(|cache| cache.get(&resolved.path, Encoding::Identity, as_index, &metadata));
+        let (body, source) = match cached {
+            Some(body) => (body, FileSource::Cache),
+            None => {
+                let read = self.async_vfs.read(&resolved.path, self.open_nofollow);
+                let body = match self.read_timeout {
+                    Some(limit) => tokio::time::timeout(limit, read)
+                        .await
+                        .map_err(|_| ServeError::Timeout)?,
+                    None => read.await,
+                }
+                .map_err(ServeError::from_io)?;
+
+                let body = self.finish_body(&resolved, &mime_type, body);
+                if let Some(cache) = cache {
+                    cache.insert(
+                        &resolved.path,
+                        Encoding::Identity,
+                        as_index,
+                        &metadata,
+                        &body,
+                    );
+                }
+                (body, FileSource::Disk)
+            }
+        };
+
+        let etag = self.served_etag(&resolved, &metadata, &mime_type, Some(&body))?;
+        let headers = self.body_headers(&resolved, &mime_type, false, &body);
+
+        Ok(StaticFile {
+            len: body.len() as u64,
+            body,
+            mime_type,
+            headers,
+            path: resolved.path,
+            status: 200,
+            etag: Some(etag),
+            modified: self.modified_time(&metadata),
+            content_encoding: None,
+            source,
+        })
+    }
+
+    // whether the served body differs from the bytes on disk
+    fn rewrites_body(&self, resolved: &Resolved, mime_type: &str) -> bool {
+        self.rewrites_by_hook(mime_type) || self.injects_base_href(resolved, mime_type)
+    }
+
+    // whether a user supplied hook may rewrite a body of the given type
+    fn rewrites_by_hook(&self, mime_type: &str) -> bool {
+        self.transform.is_some()
+            || (self.html_meta_injector.is_some() && mime_type.starts_with("text/html"))
+    }
+
+    // whether a <base href> is injected into the served body
+    fn injects_base_href(&self, resolved: &Resolved, mime_type: &str) -> bool {
+        self.inject_base_href && resolved.is_index && mime_type.starts_with("text/html")
+    }
+
+    // Subresource Integrity strings for every servable file under the root, keyed by request
+    // path (mount path included); the hashes cover the bytes read_file serves, so rewrites
+    // and transforms are accounted for. Files the server would refuse are left out, and
+    // symlinked directories are not descended into
+    pub fn sri_hashes(&self, algorithm: SriAlgo) -> HashMap<String, String> {
+        let mut hashes = HashMap::new();
+        let mut pending = vec![String::new()];
+
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = self.vfs.read_dir(&self.root_dir.join(&dir)) else {
+                continue;
+            };
+
+            for entry in entries {
+                let Some(name) = entry.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                let relative = join_relative(&dir, name);
+                let Ok(metadata) = self.vfs.symlink_metadata(&entry) else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    pending.push(relative);
+                    continue;
+                }
+
+                let Ok(resolved) = self.resolve_relative(&relative, IndexMode::Never) else {
+                    continue;
+                };
+                if !self.pre_serve_allows(&resolved) {
+                    continue;
+                }
+                let Ok(metadata) = self.vfs.metadata(&resolved.path) else {
+                    continue;
+                };
+                let mime_type = self.mime_for_resolved(&resolved);
+                if let Ok((body, _)) = self.identity_body(&resolved, &metadata, &mime_type) {
+                    hashes.insert(
+                        format!("{}{}", self.base_href(), relative),
+                        sri_integrity(algorithm, &body),
+                    );
+                }
+            }
+        }
+
+        hashes
+    }
+
+    // compare two asset roots (e.g. the outgoing and incoming deploys) by content, to know
+    // which paths to pre-warm or purge from a CDN; the server's dotfile and symlink policies
+    // decide which files count, and symlinks leading out of their root are ignored
+    pub fn diff_roots(&self, old: &Path, new: &Path) -> RootDiff {
+        RootDiff::between(&self.content_hashes(old), &self.content_hashes(new))
+    }
+
+    // a SHA-256 digest of every file under a root the server's policies would serve, keyed
+    // by path relative to the root
+    fn content_hashes(&self, root: &Path) -> BTreeMap<String, Vec<u8>> {
+        let mut hashes = BTreeMap::new();
+        let Ok(canonical_root) = self.vfs.canonicalize(root) else {
+            return hashes;
+        };
+        let mut pending = vec![String::new()];
+
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = self.vfs.read_dir(&root.join(&dir)) else {
+                continue;
+            };
+
+            for entry in entries {
+                let Some(name) = entry.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !self.serve_hidden && name.starts_with('.') {
+                    continue;
+                }
+                let Ok(metadata) = self.vfs.symlink_metadata(&entry) else {
+                    continue;
+                };
+                let relative = join_relative(&dir, name);
+                if metadata.is_dir() {
+                    pending.push(relative);
+                    continue;
+                }
+                if metadata.file_type().is_symlink() && !self.follow_symlinks {
+                    continue;
+                }
+
+                let within_root = self
+                    .vfs
+                    .canonicalize(&entry)
+                    .is_ok_and(|target| target.starts_with(&canonical_root));
+                let is_file = self.vfs.metadata(&entry).is_ok_and(|meta| meta.is_file());
+                if !within_root || !is_file {
+                    continue;
+                }
+                if let Ok(body) = self.vfs.read(&entry, false) {
+                    hashes.insert(relative, SriAlgo::Sha256.digest(&body));
+                }
+            }
+        }
+
+        hashes
+    }
+
+    // write a tar archive of the files under the directory a request path names, for a
+    // "download all" link; entries are named relative to that directory and hold the bytes
+    // on disk. Files the server would refuse (dotfiles, symlinks per policy, escapes), files
+    // which can't be read and files over max_file_size are left out, and symlinked
+    // directories aren't descended into. The files together may hold no more than
+    // max_file_size either, or the request fails with TooLarge. The archive is built in full
+    // before anything is written, so a failure never leaves a truncated archive behind
+    pub fn tar_directory(
+        &self,
+        request_path: &str,
+        writer: &mut impl Write,
+    ) -> Result<(), ServeError> {
+        let archive = self.build_tar(request_path)?;
+        writer.write_all(&archive).map_err(ServeError::Io)
+    }
+
+    // the same archive as tar_directory, gzip-compressed (a `.tar.gz`)
+    pub fn tar_gz_directory(
+        &self,
+        request_path: &str,
+        writer: &mut impl Write,
+    ) -> Result<(), ServeError> {
+        let archive = self.build_tar(request_path)?;
+        writer
+            .write_all(&gzip_encode(&archive))
+            .map_err(ServeError::Io)
+    }
+
+    // collect the archive of a requested directory in memory
+    fn build_tar(&self, request_path: &str) -> Result<Vec<u8>, ServeError> {
+        let (base, dir) = self.resolve_directory(request_path)?;
+
+        let mut tar = TarWriter::new(Vec::new());
+        let mut total: u64 = 0;
+        let mut pending = vec![String::new()];
+        while let Some(sub) = pending.pop() {
+            let Ok(mut entries) = self.vfs.read_dir(&dir.join(&sub)) else {
+                continue;
+            };
+            entries.sort();
+
+            for entry in entries {
+                let Some(name) = entry.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                let member = join_relative(&sub, name);
+                let Ok(metadata) = self.vfs.symlink_metadata(&entry) else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    pending.push(member);
+                    continue;
+                }
+
+                let from_root = join_relative(&base, &member);
+                let Ok(resolved) = self.resolve_relative(&from_root, IndexMode::Never) else {
+                    continue;
+                };
+                if !self.pre_serve_allows(&resolved) {
+                    continue;
+                }
+                let Ok(metadata) = self.vfs.metadata(&resolved.path) else {
+                    continue;
+                };
+                if self.check_file_size(&metadata).is_err() {
+                    continue;
+                }
+                let Ok(body) = self.vfs.read(&resolved.path, self.open_nofollow) else {
+                    continue;
+                };
+                total += body.len() as u64;
+                if let Some(limit) = self.max_file_size
+                    && total > limit
+                {
+                    return Err(ServeError::TooLarge { size: total, limit });
+                }
+                let mtime = self
+                    .modified_time(&metadata)
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |since_epoch| since_epoch.as_secs());
+                tar.append(&member, &body, mtime).map_err(ServeError::Io)?;
+            }
+        }
+
+        tar.finish().map_err(ServeError::Io)
+    }
+
+    // map a request path onto a directory inside the root, as (mount-relative path without
+    // surrounding slashes, path on disk), applying the same checks as for files
+    fn resolve_directory(&self, request_path: &str) -> Result<(String, PathBuf), ServeError> {
+        let relative_path = self
+            .strip_mount(strip_query(request_path))
+            .ok_or(ServeError::NotFound)?;
+        let relative_path = percent_decode_path(relative_path)?;
+        validate_components(&relative_path)?;
+        let base = relative_path.trim_matches('/').to_string();
+
+        if !self.serve_hidden && has_hidden_component(Path::new(&base)) {
+            return Err(ServeError::Forbidden);
+        }
+        let dir = self.root_dir.join(&base);
+        if !self.follow_symlinks && self.has_symlink_component(&dir) {
+            return Err(ServeError::NotFound);
+        }
+        let canonical_root = self.canonical_root().map_err(|_| ServeError::NotFound)?;
+        let canonical_dir = self
+            .canonicalize_cached(&dir)
+            .map_err(missing_unless_denied)?;
+        if !canonical_dir.starts_with(&canonical_root) || !self.is_dir(&canonical_dir) {
+            return Err(ServeError::NotFound);
+        }
+        Ok((base, dir))
+    }
+
+    // the entries of the directory a request path names, sorted by name, along with the
+    // directory's mount-relative path and its path on disk. Only entries the server would
+    // serve are included: files must resolve, and dotfiles (unless served) and symlinked
+    // directories are left out. Symlinks only resolve while follow_symlinks is on, and then
+    // carry their target's root-relative path
+    fn directory_entries(
+        &self,
+        request_path: &str,
+    ) -> Result<(String, PathBuf, Vec<DirEntryInfo>), ServeError> {
+        let (base, dir) = self.resolve_directory(request_path)?;
+        let mut paths = self.vfs.read_dir(&dir).map_err(ServeError::from_io)?;
+        paths.sort();
+
+        let mut entries = Vec::new();
+        for path in paths {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !self.serve_hidden && name.starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = self.vfs.symlink_metadata(&path) else {
+                continue;
+            };
+            let file_type = if metadata.file_type().is_symlink() {
+                FileType::Symlink
+            } else if metadata.is_dir() {
+                FileType::Dir
+            } else {
+                FileType::File
+            };
+            let mut target = None;
+            let metadata = if metadata.is_dir() {
+                metadata
+            } else {
+                let from_root = join_relative(&base, name);
+                let Ok(resolved) = self.resolve_relative(&from_root, IndexMode::Never) else {
+                    continue;
+                };
+                if !self.pre_serve_allows(&resolved) {
+                    continue;
+                }
+                let Ok(metadata) = self.vfs.metadata(&resolved.path) else {
+                    continue;
+                };
+                if file_type == FileType::Symlink {
+                    target = self.root_relative(&resolved.path);
+                }
+                metadata
+            };
+
+            entries.push(DirEntryInfo {
+                name: name.to_string(),
+                is_dir: metadata.is_dir(),
+                file_type,
+                target,
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified: metadata.modified().ok(),
+            });
+        }
+        Ok((base, dir, entries))
+    }
+
+    // the path of a canonical path inside the root relative to the root, with '/' separators
+    fn root_relative(&self, canonical: &Path) -> Option<String> {
+        let canonical_root = self.canonical_root().ok()?;
+        let relative = canonical.strip_prefix(&canonical_root).ok()?;
+        let segments: Option<Vec<&str>> = relative
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect();
+        Some(segments?.join("/"))
+    }
+
+    // the entries of the directory a request path names in the canonical listing order:
+    // subdirectories first, then files, each group sorted by name byte by byte (so
+    // case-sensitively). The order doesn't depend on the filesystem, which makes it suitable
+    // for snapshot tests, and the HTML listing uses it too
+    pub fn list_directory_sorted(
+        &self,
+        request_path: &str,
+    ) -> Result<Vec<DirEntryInfo>, ServeError> {
+        let (_, _, mut entries) = self.directory_entries(request_path)?;
+        sort_entries(&mut entries);
+        Ok(entries)
+    }
+
+    // an HTML listing of the directory a request path names, with absolute links under the
+    // mount path, in the canonical order of list_directory_sorted; symlinks are followed by an
+    // arrow and their target under the mount path
+    pub fn list_directory(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        let (base, dir, mut entries) = self.directory_entries(request_path)?;
+        sort_entries(&mut entries);
+
+        let mount = self.mount_path.trim_end_matches('/');
+        let href_for = |relative: &str| {
+            let encoded: Vec<String> = relative
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(percent_encode_segment)
+                .collect();
+            format!("{}/{}", mount, encoded.join("/"))
+        };
+
+        let links: Vec<ListingLink> = entries
+            .iter()
+            .map(|entry| {
+                let href = href_for(&join_relative(&base, &entry.name));
+                let href = if entry.is_dir {
+                    format!("{}/", href)
+                } else {
+                    href
+                };
+                ListingLink {
+                    href,
+                    name: entry.name.clone(),
+                    is_dir: entry.is_dir,
+                    target: entry
+                        .target
+                        .as_deref()
+                        .map(|target| format!("{}/{}", mount, target)),
+                }
+            })
+            .collect();
+
+        let title = format!("{}/", href_for(&base).trim_end_matches('/'));
+        let parent = (!base.is_empty()).then(|| {
+            let up = base.rsplit_once('/').map_or("", |(parent, _)| parent);
+            format!("{}/", href_for(up).trim_end_matches('/'))
+        });
+        let body = html::directory_listing(&title, parent.as_deref(), &links).into_bytes();
+        Ok(self.listing_file(body, "text/html; charset=utf-8", dir))
+    }
+
+    // a JSON listing of the directory a request path names, for programmatic clients: an
+    // array of `{"name", "is_dir", "file_type", "target", "size", "modified"}` objects sorted
+    // by name
+    pub fn list_directory_json(&self, request_path: &str) -> Result<StaticFile, ServeError> {
+        let (_, dir, entries) = self.directory_entries(request_path)?;
+        let body = entries_to_json(&entries).into_bytes();
+        Ok(self.listing_file(body, "application/json", dir))
+    }
+
+    // wrap a generated listing body for serving
+    fn listing_file(&self, body: Vec<u8>, mime_type: &'static str, dir: PathBuf) -> StaticFile {
+        StaticFile {
+            len: body.len() as u64,
+            body,
+            mime_type: Cow::Borrowed(mime_type),
+            etag: None,
+            modified: None,
+            path: dir,
+            headers: self.response_headers(),
+            status: 200,
+            content_encoding: None,
+            source: FileSource::Disk,
+        }
+    }
+
+    // the entity tag for a served body; with a transform or meta tags applied it is a weak
+    // hash of the rewritten identity bytes (computed from `identity` when the caller has them
+    // at hand), otherwise the metadata or build ID based tag from etag_for
+    fn served_etag(
+        &self,
+        resolved: &Resolved,
+        metadata: &Metadata,
+        mime_type: &str,
+        identity: Option<&[u8]>,
+    ) -> Result<String, ServeError> {
+        let combined = self.combined_index_etag && self.is_index_file(resolved);
+        let hooked = self.rewrites_by_hook(mime_type);
+        if !hooked && !combined {
+            return Ok(self.etag_for(&resolved.relative, metadata));
+        }
+
+        let mut hash = match identity {
+            Some(body) => fnv1a_64(body),
+            None => fnv1a_64(&self.identity_body(resolved, metadata, mime_type)?.0),
+        };
+        if combined {
+            hash = self.directory_hash(&resolved.path, hash);
+        }
+
+        if hooked {
+            Ok(format!("W/\"{:016x}\"", hash))
+        } else {
+            Ok(format!("\"{:016x}\"", hash))
+        }
+    }
+
+    // whether a resolved file is one of the index files, requested by name or not
+    fn is_index_file(&self, resolved: &Resolved) -> bool {
+        resolved.is_index
+            || resolved
+                .path
+                .file_name()
+                .is_some_and(|name| self.index_files.iter().any(|index| name == index.as_str()))
+    }
+
+    // fold the name and content hash of every file beside an index into the index's hash;
+    // unreadable entries, directories and (unless served) dotfiles are skipped
+    fn directory_hash(&self, index_path: &Path, index_hash: u64) -> u64 {
+        let Some(dir) = index_path.parent() else {
+            return index_hash;
+        };
+        let mut siblings = self.vfs.read_dir(dir).unwrap_or_default();
+        siblings.sort();
+
+        let mut material = index_hash.to_be_bytes().to_vec();
+        for sibling in siblings {
+            let Some(name) = sibling.file_name() else {
+                continue;
+            };
+            let skipped = sibling == index_path
+                || (!self.serve_hidden && name.to_string_lossy().starts_with('.'))
+                || !self
+                    .vfs
+                    .metadata(&sibling)
+                    .is_ok_and(|metadata| metadata.is_file());
+            if skipped {
+                continue;
+            }
+            let Ok(content) = self.vfs.read(&sibling, self.open_nofollow) else {
+                continue;
+            };
+            material.extend_from_slice(name.as_encoded_bytes());
+            material.push(0);
+            material.extend_from_slice(&fnv1a_64(&content).to_be_bytes());
+        }
+        fnv1a_64(&material)
+    }
+
+    // open the file for incremental reading instead of loading it into memory; a plain file
+    // streams straight from disk with a known length, while a rewritten body (such as an index
+    // with an injected <base href> or a transform applied) is reported as chunked
+    pub fn read_file_stream(&self, request_path: &str) -> Result<StaticFileStream, ServeError> {
+        let guard = self.acquire_stream()?;
+        let resolved = self.resolve_checked(request_path)?;
+        let mime_type = self.mime_for_resolved(&resolved);
+        let file = self
+            .open_for_read(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        let metadata = file.metadata().map_err(ServeError::from_io)?;
+
+        let mut stream = if self.rewrites_body(&resolved, &mime_type) {
+            let (body, _) = self.identity_body(&resolved, &metadata, &mime_type)?;
+            let etag = self.served_etag(&resolved, &metadata, &mime_type, Some(&body))?;
+            let mut stream = StaticFileStream::chunked(Box::new(std::io::Cursor::new(body)));
+            stream.etag = Some(etag);
+            stream
+        } else {
+            let mut stream = StaticFileStream::with_len(Box::new(file), metadata.len());
+            stream.etag = Some(self.served_etag(&resolved, &metadata, &mime_type, None)?);
+            stream
+        };
+
+        stream.headers = self.file_headers(&resolved, &mime_type);
+        stream.mime_type = mime_type;
+        stream.modified = self.modified_time(&metadata);
+        stream.path = resolved.path;
+        Ok(stream.with_guard(guard))
+    }
+
+    // resolve a request and hand back the open file with its metadata, leaving the copy to the
+    // caller (e.g. with sendfile); the bytes are the file on disk, so body rewriting such as
+    // base href injection does not apply
+    pub fn open_file(&self, request_path: &str) -> Result<(File, FileMeta), ServeError> {
+        let guard = self.acquire_stream()?;
+        let resolved = self.resolve_checked(request_path)?;
+        let file = self
+            .open_for_read(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        let metadata = file.metadata().map_err(ServeError::from_io)?;
+
+        let meta = FileMeta {
+            len: metadata.len(),
+            mime_type: self.mime_for_resolved(&resolved),
+            modified: self.modified_time(&metadata),
+            etag: Some(self.etag_for(&resolved.relative, &metadata)),
+            path: resolved.path,
+            guard: guard.map(Arc::new),
+        };
+        Ok((file, meta))
+    }
+
+    // take a slot of the open-stream limit, when one is configured
+    fn acquire_stream(&self) -> Result<Option<StreamGuard>, ServeError> {
+        match self.max_open_streams {
+            Some(limit) => StreamGuard::acquire(&self.open_streams, limit)
+                .map(Some)
+                .ok_or(ServeError::TooManyOpenStreams),
+            None => Ok(None),
+        }
+    }
+
+    // the number of streams and opened files currently holding a slot of the limit
+    pub fn open_streams(&self) -> usize {
+        self.open_streams.load(Ordering::Acquire)
+    }
+
+    // a body from the cache when it holds the current version of the file, otherwise loaded
+    // and stored for the next request; `as_index` marks a body carrying the rewrites only a
+    // served index gets, so it never stands in for the file requested by name
+    fn cached_body(
+        &self,
+        path: &Path,
+        encoding: Encoding,
+        as_index: bool,
+        metadata: &Metadata,
+        load: impl FnOnce() -> Result<Vec<u8>, ServeError>,
+    ) -> Result<(Vec<u8>, FileSource), ServeError> {
+        let Some(cache) = self
+            .cache
+            .as_ref()
+            .filter(|cache| cache.admits(metadata.len()))
+        else {
+            return Ok((load()?, FileSource::Disk));
+        };
+
+        if let Some(body) = cache.get(path, encoding, as_index, metadata) {
+            return Ok((body, FileSource::Cache));
+        }
+
+        let body = load()?;
+        cache.insert(path, encoding, as_index, metadata, &body);
+        Ok((body, FileSource::Disk))
+    }
+
+    // evict every cached encoding of the file a request resolves to, along with its
+    // precompressed siblings, returning whether anything was removed
+    pub fn invalidate(&self, request_path: &str) -> bool {
+        // a file which just appeared must not be hidden by a remembered miss, including the
+        // miss for a lone `.gz` standing in for it
+        let forgot_miss = self.negative_cache.as_ref().is_some_and(|negative| {
+            let gzip_only = self.sibling_suffix(Encoding::Gzip).is_some_and(|suffix| {
+                negative.remove(&format!("{}{}", strip_query(request_path), suffix))
+            });
+            negative.remove(request_path) || gzip_only
+        });
+
+        let evicted = match (&self.cache, self.resolve_checked(request_path)) {
+            (Some(cache), Ok(resolved)) => {
+                // siblings are cached under their own paths
+                let siblings = self.precompressed_extensions.values().map(|suffix| {
+                    let mut sibling = resolved.path.as_os_str().to_owned();
+                    sibling.push(suffix);
+                    PathBuf::from(sibling)
+                });
+                std::iter::once(resolved.path.clone())
+                    .chain(siblings)
+                    .filter(|path| cache.invalidate(path))
+                    .count()
+                    > 0
+            }
+            _ => false,
+        };
+        forgot_miss || evicted
+    }
+
+    // evict every cached body and forget every remembered miss
+    pub fn invalidate_all(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_all();
+        }
+        if let Some(negative) = &self.negative_cache {
+            negative.clear();
+        }
+    }
+
+    // a snapshot of the negative cache counters, or None when it is disabled
+    pub fn negative_cache_stats(&self) -> Option<NegativeCacheStats> {
+        self.negative_cache.as_ref().map(NegativeCache::stats)
+    }
+
+    // a snapshot of the cache counters, or None when caching is disabled
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(FileCache::stats)
+    }
+
+    // copy one byte range of a file into a writer, seeking to the start and streaming exactly
+    // end - start + 1 bytes without buffering the whole range in memory; ranges always apply
+    // to the identity bytes, never to a compressed variant (see negotiate_encoding). None,
+    // with nothing written, means the Range header is ignored and the full file is due; that
+    // is also the answer for a body the server rewrites, since ranges of the file on disk
+    // wouldn't match the representation its ETag describes. A served range is logged as a 206
+    pub fn write_range_to<W: Write>(
+        &self,
+        request_path: &str,
+        range: &str,
+        writer: &mut W,
+    ) -> Result<Option<RangeMeta>, ServeError> {
+        let result = self.write_range_unlogged(request_path, range, writer);
+        if let Some(sink) = &self.access_log {
+            let record = match &result {
+                Ok(Some((meta, path))) => AccessLogRecord {
+                    request_path: request_path.to_string(),
+                    resolved_path: Some(path.clone()),
+                    status: 206,
+                    bytes: meta.len(),
+                    mime_type: Some(meta.mime_type.to_string()),
+                },
+                // the full file is left to the caller, which logs it
+                Ok(None) => return Ok(None),
+                Err(err) => AccessLogRecord {
+                    request_path: request_path.to_string(),
+                    resolved_path: None,
+                    status: err.status_code(),
+                    bytes: 0,
+                    mime_type: None,
+                },
+            };
+            sink(&record);
+        }
+        result.map(|served| served.map(|(meta, _)| meta))
+    }
+
+    // the range copy behind write_range_to, without writing to the access log; a served range
+    // comes with the path of the file it was cut from
+    fn write_range_unlogged<W: Write>(
+        &self,
+        request_path: &str,
+        range: &str,
+        writer: &mut W,
+    ) -> Result<Option<(RangeMeta, PathBuf)>, ServeError> {
+        let resolved = self.resolve_checked(request_path)?;
+        // a replacement is only served whole, by read_file
+        if self
+            .check_pre_serve(&resolved, &RequestContext::default())?
+            .is_some()
+        {
+            return Ok(None);
+        }
+        let mime_type = self.mime_for_resolved(&resolved);
+        if self.rewrites_body(&resolved, &mime_type) {
+            return Ok(None);
+        }
+        let mut file = self
+            .open_for_read(&resolved.path)
+            .map_err(ServeError::from_io)?;
+        let metadata = file.metadata().map_err(ServeError::from_io)?;
+        self.check_file_size(&metadata)?;
+        let size = metadata.len();
+
+        let Some((start, end)) = parse_range(range, size)? else {
+            return Ok(None);
+        };
+        let meta = RangeMeta {
+            start,
+            end,
+            size,
+            mime_type,
+        };
+
+        file.seek(SeekFrom::Start(start))
+            .map_err(ServeError::from_io)?;
+        let copied = std::io::copy(&mut file.take(meta.len()), writer).map_err(ServeError::Io)?;
+
+        // the file shrank underneath us
+        if copied != meta.len() {
+            return Err(ServeError::Io(ErrorKind::UnexpectedEof.into()));
+        }
+
+        Ok(Some((meta, resolved.path)))
+    }
+
+    // read one byte range of a file into memory; only the requested bytes are read, so this
+    // suits seeking in large media files where write_range_to's writer isn't convenient.
+    // None means the Range header is ignored and the full file (read_file) is due
+    pub fn read_range(
+        &self,
+        request_path: &str,
+        range: &str,
+    ) -> Result<Option<StaticFileRange>, ServeError> {
+        let mut body = Vec::new();
+        let meta = self.write_range_to(request_path, range, &mut body)?;
+        Ok(meta.map(|meta| StaticFileRange { body, meta }))
+    }
+
+    // hand the outcome of a request to the access log sink, if one is set
+    fn log_access(&self, request_path: &str, result: &Result<StaticFile, ServeError>) {
+        let Some(sink) = &self.access_log else {
+            return;
+        };
+
+        let record = match result {
+            Ok(file) => AccessLogRecord {
+                request_path: request_path.to_string(),
+                resolved_path: Some(file.path.clone()),
+                status: file.status,
+                bytes: file.body.len() as u64,
+                mime_type: Some(file.mime_type.to_string()),
+            },
+            Err(err) => AccessLogRecord {
+                request_path: request_path.to_string(),
+                resolved_path: None,
+                status: err.status_code(),
+                bytes: 0,
+                mime_type: None,
+            },
+        };
+
+        sink(&record);
+    }
+
+    // the MIME type a request path would be served with, judged purely from the path;
+    // the filesystem is never touched, so the file does not need to exist
+    pub fn mime_for_request(&self, request_path: &str) -> Cow<'static, str> {
+        let relative_path = self.strip_mount(request_path).unwrap_or(request_path);
+        match percent_decode_path(relative_path) {
+            Ok(decoded) => self.mime_with_default_charset(&decoded),
+            Err(_) => self.mime_with_default_charset(relative_path),
+        }
+    }
+
+    // strip the mount path from a request path, returning the root-relative remainder
+    fn strip_mount<'a>(&self, request_path: &'a str) -> Option<&'a str> {
+        let rest = request_path.strip_prefix(&self.mount_path)?;
+        // the mount path has to end on a segment boundary, so `/static` doesn't claim
+        // `/statichack.txt`; the root mount ends in a slash and always does
+        if !(rest.is_empty() || rest.starts_with('/') || self.mount_path.ends_with('/')) {
+            return None;
+        }
+        Some(rest.trim_start_matches('/'))
+    }
+
+    // pick the MIME type for a mount-relative path served by this server; a forced
+    // prefix wins over extension guessing, and the longest matching prefix wins
+    fn mime_for_relative(&self, relative_path: &str) -> Cow<'static, str> {
+        let forced = self
+            .force_mime_for_prefix
+            .iter()
+            .filter(|(prefix, _)| is_path_prefix(prefix, relative_path))
+            .max_by_key(|(prefix, _)| prefix.len());
+
+        if let Some((_, mime_type)) = forced {
+            return Cow::Owned(mime_type.clone());
+        }
+
+        let overridden = Path::new(relative_path).extension().and_then(|extension| {
+            self.mime_overrides
+                .get(&extension.to_string_lossy().to_ascii_lowercase())
+        });
+        match overridden {
+            Some(mime_type) => Cow::Owned(mime_type.clone()),
+            None => guess_mime_type(Path::new(relative_path)),
+        }
+    }
+
+    // the MIME type of a resolved file; when the path alone yields octet-stream, the file's
+    // first bytes are matched against the sniff table. Text types get a charset detected from
+    // a byte order mark, or else the default one
+    fn mime_for_resolved(&self, resolved: &Resolved) -> Cow<'static, str> {
+        let mime_type = self.sniffed_mime(resolved);
+        if !is_textual(&mime_type) || mime_type.contains("charset=") {
+            return mime_type;
+        }
+
+        let detected = if self.detect_charset {
+            self.read_prefix(&resolved.path, 3)
+                .and_then(|prefix| bom_charset(&prefix))
+        } else {
+            None
+        };
+        match detected.or(self.default_charset.as_deref()) {
+            Some(charset) => Cow::Owned(format!("{}; charset={}", mime_type, charset)),
+            None => mime_type,
+        }
+    }
+
+    // the MIME type for a mount-relative path with the default charset added to text types,
+    // for bodies whose file isn't inspected
+    fn mime_with_default_charset(&self, relative_path: &str) -> Cow<'static, str> {
+        let mime_type = self.mime_for_relative(relative_path);
+        match &self.default_charset {
+            Some(charset) if is_textual(&mime_type) && !mime_type.contains("charset=") => {
+                Cow::Owned(format!("{}; charset={}", mime_type, charset))
+            }
+            _ => mime_type,
+        }
+    }
+
+    // the MIME type from the path, falling back to the sniff table for octet-stream files
+    fn sniffed_mime(&self, resolved: &Resolved) -> Cow<'static, str> {
+        let mime_type = self.mime_for_relative(&resolved.relative);
+        if mime_type != "application/octet-stream" || self.sniff_table.is_empty() {
+            return mime_type;
+        }
+
+        let longest = self
+            .sniff_table
+            .iter()
+            .map(|(magic, _)| magic.len())
+            .max()
+            .unwrap_or(0);
+        let Some(prefix) = self.read_prefix(&resolved.path, longest) else {
+            return mime_type;
+        };
+
+        self.sniff_table
+            .iter()
+            .find(|(magic, _)| !magic.is_empty() && prefix.starts_with(magic))
+            .map_or(mime_type, |(_, sniffed)| Cow::Owned(sniffed.clone()))
+    }
+
+    // the first bytes of a file, up to len; None when it can't be read
+    fn read_prefix(&self, path: &Path, len: usize) -> Option<Vec<u8>> {
+        let mut prefix = Vec::with_capacity(len);
+        self.open_for_read(path)
+            .and_then(|file| file.take(len as u64).read_to_end(&mut prefix))
+            .ok()?;
+        Some(prefix)
+    }
+
+    // open a resolved file for reading, honouring the O_NOFOLLOW option
+    fn open_for_read(&self, path: &Path) -> std::io::Result<File> {
+        self.vfs.open(path, self.open_nofollow)
+    }
+
+    // whether a path is an existing directory
+    fn is_dir(&self, path: &Path) -> bool {
+        self.vfs
+            .metadata(path)
+            .is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    // whether any component between the root directory and path is a symlink
+    fn has_symlink_component(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root_dir) else {
+            return false;
+        };
+
+        let mut current = self.root_dir.clone();
+        for component in relative.components() {
+            current.push(component);
+            match self.vfs.symlink_metadata(&current) {
+                Ok(metadata) if metadata.file_type().is_symlink() => return true,
+                Ok(_) => {}
+                // nothing further exists; resolution will fail on its own
+                Err(_) => return false,
+            }
+        }
+
+        false
+    }
+
+    // canonicalize a requested path, going through the symlink cache when it is enabled;
+    // callers still check containment and existence on the result every time
+    fn canonicalize_cached(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let Some(cache) = self.symlink_cache.as_ref().filter(|_| self.follow_symlinks) else {
+            return self.vfs.canonicalize(path);
+        };
+
+        if let Some(canonical) = cache.get(path) {
+            return Ok(canonical);
+        }
+
+        let canonical = self.vfs.canonicalize(path)?;
+        cache.insert(path.to_path_buf(), canonical.clone());
+        Ok(canonical)
+    }
+
+    // forget every cached symlink resolution; hook this up to a file watcher to pick up
+    // retargeted symlinks before the cache TTL runs out
+    pub fn clear_symlink_cache(&self) {
+        if let Some(cache) = &self.symlink_cache {
+            cache.clear();
+        }
+    }
+
+    // replace the filesystem the server reads from
+    pub fn set_vfs(&mut self, vfs: Arc<dyn Vfs>) {
+        self.vfs = vfs;
+        // a root resolved through the previous filesystem may not hold for this one
+        self.canonical_root = self.vfs.canonicalize(&self.root_dir).ok();
+    }
+
+    // whether index resolution is switched off for a mount-relative path
+    fn index_disabled_for(&self, relative_path: &str) -> bool {
+        let relative_path = relative_path.trim_end_matches('/');
+        self.no_index_prefixes
+            .iter()
+            .any(|prefix| is_path_prefix(prefix, relative_path))
+    }
+
+    // the error for a directory request that isn't answered with an index
+    fn bare_directory_error(&self) -> ServeError {
+        match &self.bare_directory {
+            BareDirectoryPolicy::NotFound => ServeError::NotFound,
+            BareDirectoryPolicy::Forbidden => ServeError::Forbidden,
+            BareDirectoryPolicy::Redirect(location) => ServeError::Redirect {
+                status: 302,
+                location: location.clone(),
+            },
+        }
+    }
+
+    // the index file name to serve for a directory; with a single candidate the filesystem
+    // isn't consulted, and when none of several candidates exist the first one is returned
+    // so resolution reports the miss. FirstMatch stops statting at the first existing file
+    fn select_index(&self, dir: &Path) -> Option<&str> {
+        if self.index_files.len() <= 1 {
+            return self.index_files.first().map(String::as_str);
+        }
+
+        let mut existing = self.index_files.iter().filter_map(|name| {
+            let metadata = self.vfs.metadata(&dir.join(name)).ok()?;
+            metadata.is_file().then(|| (name, metadata.modified().ok()))
+        });
+
+        let selected = match self.index_selection {
+            IndexSelection::FirstMatch => existing.next(),
+            IndexSelection::NewestModified => {
+                existing.fold(None, |newest, candidate| match newest {
+                    Some((_, newest_mtime)) if newest_mtime >= candidate.1 => newest,
+                    _ => Some(candidate),
+                })
+            }
+        };
+
+        selected
+            .map(|(name, _)| name)
+            .or(self.index_files.first())
+            .map(String::as_str)
+    }
+
+    // the href used for an injected <base> tag: the mount path with a trailing slash
+    fn base_href(&self) -> String {
+        if self.mount_path.ends_with('/') {
+            self.mount_path.clone()
+        } else {
+            format!("{}/", self.mount_path)
+        }
+    }
+
+    // the modification time reported for a file: fixed_last_modified when set, otherwise the
+    // file's own mtime
+    fn modified_time(&self, metadata: &Metadata) -> Option<SystemTime> {
+        self.fixed_last_modified
+            .or_else(|| metadata.modified().ok())
+    }
+
+    // the entity tag for a served file; with a build ID this is `"<build_id>-<path-hash>"`,
+    // identical on every replica running the same build and free of any content hashing,
+    // otherwise it is `"<len>-<mtime>"` in hex, taken from the file's metadata (with the mtime
+    // overridden by fixed_last_modified)
+    fn etag_for(&self, relative_path: &str, metadata: &Metadata) -> String {
+        match &self.build_id {
+            Some(build_id) => format!(
+                "\"{}-{:016x}\"",
+                build_id,
+                fnv1a_64(relative_path.as_bytes())
+            ),
+            None => {
+                let mtime = self
+                    .modified_time(metadata)
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |since_epoch| since_epoch.as_nanos());
+                format!("\"{:x}-{:x}\"", metadata.len(), mtime)
+            }
+        }
+    }
+
+    // whether the client's cached copy is still fresh, given its conditional request headers;
+    // when If-None-Match is present it alone decides (ETag wins over the date), otherwise
+    // If-Modified-Since is compared against the file's mtime, and with neither it is stale
+    pub fn is_fresh(
+        &self,
+        request_path: &str,
+        if_none_match: Option<&str>,
+        if_modified_since: Option<SystemTime>,
+    ) -> Result<bool, ServeError> {
+        let resolved = self.resolve_checked(request_path)?;
+        let metadata = self
+            .vfs
+            .metadata(&resolved.path)
+            .map_err(ServeError::from_io)?;
+
+        if let Some(if_none_match) = if_none_match {
+            let mime_type = self.mime_for_relative(&resolved.relative);
+            let etag = self.served_etag(&resolved, &metadata, &mime_type, None)?;
+            // a tag handed out with an encoded body names the same file
+            return Ok([Encoding::Identity, Encoding::Gzip, Encoding::Brotli]
+                .into_iter()
+                .any(|encoding| etag_matches(if_none_match, &encoded_etag(&etag, encoding))));
+        }
+
+        match (if_modified_since, self.modified_time(&metadata)) {
+            (Some(since), Some(modified)) => Ok(not_modified_since(modified, since)),
+            _ => Ok(false),
+        }
+    }
+
+    // read the file unless the client's cached copy is still fresh, in which case a 304 with
+    // an empty body (but the validators and headers a 200 would carry) is returned; the
+    // validators are weighed exactly as in is_fresh. A path whose validators can't be weighed
+    // is left to read_file, so its error reaches the error pages and the access log as usual
+    pub fn read_file_conditional(
+        &self,
+        request_path: &str,
+        if_none_match: Option<&str>,
+        if_modified_since: Option<SystemTime>,
+    ) -> Result<StaticFile, ServeError> {
+        let conditional = if_none_match.is_some() || if_modified_since.is_some();
+        if !conditional
+            || !matches!(
+                self.is_fresh(request_path, if_none_match, if_modified_since),
+                Ok(true)
+            )
+        {
+            return self.read_file(request_path);
+        }
+
+        let result = self
+            .read_head_unlogged(request_path)
+            .map(|mut not_modified| {
+                not_modified.body = Vec::new();
+                not_modified.status = 304;
+                not_modified
+            })
+            .or_else(|err| self.error_page_for(err));
+        self.log_access(request_path, &result);
+        result
+    }
+
+    // headers attached to every file this server serves
+    fn response_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+
+        if self.nosniff {
+            headers.push(("X-Content-Type-Options", "nosniff".to_string()));
+        }
+        if let Some(cache_control) = &self.cache_control {
+            headers.push(("Cache-Control", cache_control.clone()));
+        }
+        if let Some(alt_svc) = &self.alt_svc {
+            headers.push(("Alt-Svc", alt_svc.clone()));
+        }
+        if let Some(origin) = &self.timing_allow_origin {
+            headers.push(("Timing-Allow-Origin", origin.clone()));
+        }
+        if let Some(policy) = &self.cross_origin_resource_policy {
+            headers.push(("Cross-Origin-Resource-Policy", policy.clone()));
+        }
+        if let Some(policy) = &self.cross_origin_embedder_policy {
+            headers.push(("Cross-Origin-Embedder-Policy", policy.clone()));
+        }
+
+        headers
+    }
+
+    // utility to return the mount path
+    pub fn mount_path(&self) -> &str {
+        &self.mount_path
+    }
+
+    // utility to return the root dir
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
+    // utility to return whether serving index.html is true or false
+    pub fn serve_index(&self) -> bool {
+        self.serve_index
+    }
+
+    // utility to return the maximum accepted request path length
+    pub fn max_path_len(&self) -> Option<usize> {
+        self.max_path_len
+    }
+
+    // utility to return whether dotfiles are served
+    pub fn serve_hidden(&self) -> bool {
+        self.serve_hidden
+    }
+
+    // utility to return whether symlinks are followed
+    pub fn follow_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    // utility to return the Cache-Control value attached to served files, with any stale-*
+    // extensions included
+    pub fn cache_control(&self) -> Option<&str> {
+        self.cache_control.as_deref()
+    }
+}
+
+// methods for the StaticFile type
+impl StaticFile {
+    // build a 200 response around an in-memory body, for tests and dynamic content that
+    // never touches the filesystem
+    pub fn from_bytes(body: Vec<u8>, mime_type: impl Into<Cow<'static, str>>) -> Self {
+        StaticFile {
+            len: body.len() as u64,
+            body,
+            mime_type: mime_type.into(),
+            path: PathBuf::new(),
+            headers: Vec::new(),
+            status: 200,
+            etag: None,
+            modified: None,
+            content_encoding: None,
+            source: FileSource::Disk,
+        }
+    }
+
+    // the value of a response header, matched ignoring ASCII case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // the Cache-Control value to send with this file, if any
+    pub fn cache_control(&self) -> Option<&str> {
+        self.header("Cache-Control")
+    }
+
+    // set the entity tag sent in the ETag header
+    pub fn with_etag(mut self, etag: impl Into<String>) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    // set the Cache-Control header, replacing any previous value
+    pub fn with_cache_control(self, value: impl Into<String>) -> Self {
+        self.with_header("Cache-Control", value)
+    }
+
+    // set a response header, replacing any previous value of the same name
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    // set the HTTP status to respond with
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+// helper function to spot a content hash in a file name: a segment between the first and the
+// last (split at `.` and `-`) mixing letters and digits, as bundlers emit; either 6 to 64 hex
+// digits or 8 to 64 base32/base64url characters. Dates and timestamps, all digits, don't count
+fn has_content_hash(file_name: &str) -> bool {
+    let segments: Vec<&str> = file_name.split(['.', '-']).collect();
+    segments.len() >= 3
+        && segments[1..segments.len() - 1].iter().any(|segment| {
+            let hex = segment.bytes().all(|b| b.is_ascii_hexdigit());
+            let base64url = segment
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_');
+            let long_enough = match (hex, base64url) {
+                (true, _) => segment.len() >= 6,
+                (false, true) => segment.len() >= 8,
+                (false, false) => false,
+            };
+            long_enough
+                && segment.len() <= 64
+                && segment.bytes().any(|b| b.is_ascii_digit())
+                && segment.bytes().any(|b| b.is_ascii_alphabetic())
+        })
+}
+
+// helper function to guess the mime type
+pub fn guess_mime_type(path: &Path) -> Cow<'static, str> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    Cow::Owned(
+        mime_guess::from_ext(&extension)
+            .first_or_octet_stream()
+            .to_string(),
+    )
+}
+
+// helper function to list every MIME type known for the path's extension, most common first;
+// guess_mime_type picks the first of these
+pub fn guess_mime_types(path: &Path) -> Vec<String> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    mime_guess::from_ext(&extension)
+        .iter()
+        .map(|mime| mime.to_string())
+        .collect()
+}
+
+// helper function to find the tenant directory a mount-relative path belongs to: its first
+// segment, when something follows it; a path climbing out with `..` first has no tenant
+fn tenant_of(relative_path: &str) -> Result<Option<&Path>, ServeError> {
+    let mut components = Path::new(relative_path)
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir));
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(tenant)), Some(_)) => Ok(Some(Path::new(tenant))),
+        (Some(Component::ParentDir), _) => Err(ServeError::NotFound),
+        _ => Ok(None),
+    }
+}
+
+// helper function to tell whether a MIME type is text a charset parameter applies to
+fn is_textual(mime_type: &str) -> bool {
+    mime_type.starts_with("text/")
+        || mime_type.ends_with("+xml")
+        || mime_type.ends_with("+json")
+        || matches!(
+            mime_type,
+            "application/javascript" | "application/json" | "application/xml"
+        )
+}
+
+// helper function to tell whether gzip is worth applying to a MIME type; images, audio,
+// video, fonts and archives are already compressed (SVG, being XML, is the exception)
+fn is_compressible(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or("").trim();
+    is_textual(essence)
+        || matches!(
+            essence,
+            "image/svg+xml" | "application/wasm" | "application/manifest+json"
+        )
+}
+
+// helper function to name the charset announced by a byte order mark; a UTF-16 BOM gives
+// `utf-16` either way round, since the mark itself tells the reader the byte order
+fn bom_charset(prefix: &[u8]) -> Option<&'static str> {
+    if prefix.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some("utf-8")
+    } else if prefix.starts_with(&[0xFF, 0xFE]) || prefix.starts_with(&[0xFE, 0xFF]) {
+        Some("utf-16")
+    } else {
+        None
+    }
+}
+
+// helper function to mark an entity tag with the content coding of the body it labels, as in
+// `"1a-2b-gzip"`; the identity tag is returned unchanged
+fn encoded_etag(etag: &str, encoding: Encoding) -> String {
+    match (encoding, etag.strip_suffix('"')) {
+        (Encoding::Identity, _) | (_, None) => etag.to_string(),
+        (_, Some(open)) => format!("{}-{}\"", open, encoding.as_str()),
+    }
+}
+
+// helper function to pull the lowercased host out of an absolute Referer URL
+fn referer_host(referer: &str) -> Option<String> {
+    let (_, rest) = referer.split_once("://")?;
+    authority_host(rest)
+}
+
+// helper function to pull the lowercased host out of a URL authority (or a Host header),
+// dropping any userinfo, port and trailing path
+fn authority_host(authority: &str) -> Option<String> {
+    let authority = authority.split(['/', '?', '#']).next()?;
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host_port.strip_prefix('[') {
+        // an IPv6 literal keeps its brackets
+        Some(v6) => &host_port[..v6.find(']')? + 2],
+        None => host_port.split(':').next()?,
+    };
+    let host = host.trim_end_matches('.');
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+// helper function to cut a request path at the first `?` or `#`
+fn strip_query(request_path: &str) -> &str {
+    request_path
+        .split_once(['?', '#'])
+        .map_or(request_path, |(path, _)| path)
+}
+
+// helper function to lowercase configured extensions and drop any leading dot
+fn normalize_extensions(extensions: HashSet<String>) -> HashSet<String> {
+    extensions
+        .into_iter()
+        .map(|extension| extension.trim_start_matches('.').to_ascii_lowercase())
+        .collect()
+}
+
+// helper function to map a failed lookup on the way to a file: a permission problem is
+// reported as Forbidden, anything else is a miss
+fn missing_unless_denied(err: std::io::Error) -> ServeError {
+    match err.kind() {
+        ErrorKind::PermissionDenied => ServeError::Forbidden,
+        _ => ServeError::NotFound,
+    }
+}
+
+// helper function to append the stale-* extensions (RFC 5861) to a Cache-Control directive
+fn compose_cache_control(
+    directive: String,
+    stale_while_revalidate: Option<Duration>,
+    stale_if_error: Option<Duration>,
+) -> String {
+    let mut directive = directive;
+    if let Some(window) = stale_while_revalidate {
+        directive.push_str(&format!(", stale-while-revalidate={}", window.as_secs()));
+    }
+    if let Some(window) = stale_if_error {
+        directive.push_str(&format!(", stale-if-error={}", window.as_secs()));
+    }
+    directive
+}
+
+// helper function to append a file name to a mount-relative directory path
+fn join_relative(dir: &str, name: &str) -> String {
+    let dir = dir.trim_matches('/');
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+// helper function to check whether a prefix covers a path on a segment boundary
+fn is_path_prefix(prefix: &str, path: &str) -> bool {
+    if prefix.is_empty() {
+        return true;
+    }
+
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+// helper function to reject request path segments which mean something special to some
+// platform's filesystem, regardless of the platform we run on: a `:` can name a Windows
+// drive (`C:`) or an NTFS alternate data stream (`file.txt:stream`), and Windows drops a
+// trailing dot or space, so `secret.txt.` would alias `secret.txt`
+fn validate_components(relative_path: &str) -> Result<(), ServeError> {
+    for segment in relative_path.split(['/', '\\']) {
+        if segment.contains(':') {
+            return Err(ServeError::InvalidPath);
+        }
+        // `.` and `..` are left to the traversal checks
+        let dot_segment = segment == "." || segment == "..";
+        if !dot_segment && (segment.ends_with('.') || segment.ends_with(' ')) {
+            return Err(ServeError::InvalidPath);
+        }
+    }
+
+    Ok(())
+}
+
+// helper function to check whether any component of a relative path is a dotfile
+fn has_hidden_component(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+// helper function to normalize the mount path of the StaticServer
+fn normalize_mount_path(path: &str) -> String {
+    if path == "/" {
+        return "/".to_string();
+    }
+
+    let trimmed = path.trim_end_matches('/');
+
+    if trimmed.starts_with('/') {
+        trimmed.to_string()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheConfig, FileSource};
+    use crate::conditional::{format_http_date, parse_http_date};
+    use crate::stream::StreamMode;
+    use std::collections::HashMap;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn serves_existing_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("hello.txt");
+
+        let mut file = File::create(&file_path).unwrap();
+        write!(file, "Hello, world!").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_index: false,
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        let result = server.read_file("/static/hello.txt");
+
+        assert!(result.is_ok(), "Expected file to be served successfully");
+
+        let static_file = result.unwrap();
+        assert_eq!(static_file.mime_type, "text/plain");
+        assert_eq!(static_file.body, b"Hello, world!");
+    }
+
+    #[test]
+    fn returns_not_found_for_missing_file() {
+        let dir = tempdir().unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_index: false,
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        let result = server.read_file("/static/missing.txt");
+
+        assert!(matches!(result, Err(ServeError::NotFound)));
+    }
+
+    #[test]
+    fn serves_index_file_when_directory_requested() {
+        let dir = tempdir().unwrap();
+        let subdir = dir.path().join("docs");
+        fs::create_dir(&subdir).unwrap();
+
+        let index_path = subdir.join("index.html");
+        let mut index_file = File::create(&index_path).unwrap();
+        write!(index_file, "<h1>Index</h1>").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_index: true,
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        let result = server.read_file("/static/docs");
+
+        assert!(result.is_ok(), "Expected index.html to be served");
+
+        let static_file = result.unwrap();
+        assert_eq!(static_file.mime_type, "text/html");
+        assert_eq!(static_file.body, b"<h1>Index</h1>");
+    }
+
+    #[test]
+    fn directory_without_index_returns_not_found() {
+        let dir = tempdir().unwrap();
+        let subdir = dir.path().join("images");
+        fs::create_dir(&subdir).unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_index: true,
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        let result = server.read_file("/static/images");
+
+        assert!(matches!(result, Err(ServeError::NotFound)));
+    }
+
+    #[test]
+    fn rejects_request_path_exceeding_limit() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("hello.txt"), "Hello").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            max_path_len: Some(32),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        assert!(server.read_file("/static/hello.txt").is_ok());
+
+        let long_path = format!("/static/{}", "a".repeat(64));
+        let result = server.read_file(&long_path);
+
+        assert!(matches!(result, Err(ServeError::InvalidPath)));
+        assert!(server.resolve(&long_path).is_none());
+    }
+
+    #[test]
+    fn cached_index_rewrites_stay_with_the_index() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("app")).unwrap();
+        let page = "<html><head><title>App</title></head></html>";
+        fs::write(dir.path().join("app/index.html"), page).unwrap();
+        let injected = "<html><head><base href=\"/static/\"><title>App</title></head></html>";
+
+        for index_first in [true, false] {
+            let server = StaticServer::builder(dir.path())
+                .mount_path("/static")
+                .serve_index(true)
+                .inject_base_href(true)
+                .compress(true)
+                .compress_min_size(0)
+                .cache(CacheConfig::default())
+                .build()
+                .unwrap();
+            let mut order = ["/static/app/", "/static/app/index.html"];
+            if !index_first {
+                order.reverse();
+            }
+            for _ in 0..2 {
+                for path in order {
+                    let gzip = server.read_file_negotiated(path, Some("gzip")).unwrap();
+                    let body = gzip_decode(&gzip.body, None).unwrap();
+                    let identity = server.read_file(path).unwrap().body;
+                    let expected = if path.ends_with('/') { injected } else { page };
+                    assert_eq!(identity, expected.as_bytes(), "{path}");
+                    assert_eq!(body, expected.as_bytes(), "{path}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn injects_base_href_into_served_index() {
+        let dir = tempdir().unwrap();
+        let app = dir.path().join("app");
+        fs::create_dir(&app).unwrap();
+        fs::write(
+            app.join("index.html"),
+            "<html><head><title>App</title></head></html>",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("index.html"),
+            "<html><head><base href=\"/custom/\"></head></html>",
+        )
+        .unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_index: true,
+            inject_base_href: true,
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        let index = server.read_file("/static/app").unwrap();
+        assert_eq!(
+            index.body,
+            b"<html><head><base href=\"/static/\"><title>App</title></head></html>"
+        );
+
+        // an index which already declares a base is left alone
+        let existing = server.read_file("/static/").unwrap();
+        assert_eq!(
+            existing.body,
+            b"<html><head><base href=\"/custom/\"></head></html>"
+        );
+
+        // direct requests for the same file are not rewritten
+        let direct = server.read_file("/static/app/index.html").unwrap();
+        assert_eq!(direct.body, b"<html><head><title>App</title></head></html>");
+    }
+
+    #[test]
+    fn mime_for_request_does_not_need_the_file() {
+        let dir = tempdir().unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        assert_eq!(server.mime_for_request("/static/css/site.css"), "text/css");
+        assert_eq!(
+            server.mime_for_request("/static/no-extension"),
+            "application/octet-stream"
+        );
+        assert!(server.resolve("/static/css/site.css").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_file_is_forbidden() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // root bypasses file permissions, so the check can't be observed
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("locked.txt");
+        fs::write(&file_path, "secret").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        // the file exists, so resolution succeeds, but reading it is refused
+        assert!(server.resolve("/static/locked.txt").is_some());
+        let result = server.read_file("/static/locked.txt");
+
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(result, Err(ServeError::Forbidden)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unsearchable_directory_is_forbidden_not_missing() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // root bypasses file permissions, so the check can't be observed
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+
+        let dir = tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("page.html"), "secret").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .directory_listing(true)
+            .build()
+            .unwrap();
+        let file = server.read_file("/locked/page.html");
+        let listing = server.list_directory("/locked/");
+        let missing = server.read_file("/absent/page.html");
+
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(matches!(file, Err(ServeError::Forbidden)));
+        assert!(matches!(listing, Err(ServeError::Forbidden)));
+        assert!(matches!(missing, Err(ServeError::NotFound)));
+    }
+
+    #[test]
+    fn access_log_receives_a_record_per_request() {
+        use std::sync::{Arc, Mutex};
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("hello.txt"), "Hello").unwrap();
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink_records = Arc::clone(&records);
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .access_log(move |record| sink_records.lock().unwrap().push(record.clone()))
+            .build()
+            .unwrap();
+
+        server.read_file("/static/hello.txt").unwrap();
+        server.read_file("/static/missing.txt").unwrap_err();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].request_path, "/static/hello.txt");
+        assert_eq!(records[0].status, 200);
+        assert_eq!(records[0].bytes, 5);
+        assert_eq!(records[0].mime_type.as_deref(), Some("text/plain"));
+        assert!(
+            records[0]
+                .resolved_path
+                .as_ref()
+                .unwrap()
+                .ends_with("hello.txt")
+        );
+
+        assert_eq!(records[1].status, 404);
+        assert_eq!(records[1].bytes, 0);
+        assert_eq!(records[1].resolved_path, None);
+    }
+
+    #[test]
+    fn no_index_prefixes_skip_index_resolution() {
+        let dir = tempdir().unwrap();
+        for subdir in ["api-docs", "downloads/v1", "downloads-archive"] {
+            fs::create_dir_all(dir.path().join(subdir)).unwrap();
+            fs::write(dir.path().join(subdir).join("index.html"), subdir).unwrap();
+        }
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_index: true,
+            no_index_prefixes: vec!["/downloads/".to_string()],
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        assert_eq!(
+            server.read_file("/static/api-docs/").unwrap().body,
+            b"api-docs"
+        );
+        assert!(matches!(
+            server.read_file("/static/downloads/v1"),
+            Err(ServeError::NotFound)
+        ));
+        // the prefix matches whole segments only
+        assert_eq!(
+            server.read_file("/static/downloads-archive").unwrap().body,
+            b"downloads-archive"
+        );
+        // explicit requests for the file are still served
+        assert!(server.read_file("/static/downloads/v1/index.html").is_ok());
+    }
+
+    #[test]
+    fn forced_mime_for_prefix_overrides_guessing() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("api/v2")).unwrap();
+        fs::write(dir.path().join("api/config"), "{}").unwrap();
+        fs::write(dir.path().join("api/v2/schema"), "<x/>").unwrap();
+        fs::write(dir.path().join("apiary.txt"), "bees").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            force_mime_for_prefix: HashMap::from([
+                ("api/".to_string(), "application/json".to_string()),
+                ("api/v2".to_string(), "application/xml".to_string()),
+            ]),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        let json = server.read_file("/static/api/config").unwrap();
+        assert_eq!(json.mime_type, "application/json");
+
+        let xml = server.read_file("/static/api/v2/schema").unwrap();
+        assert_eq!(xml.mime_type, "application/xml");
+
+        let text = server.read_file("/static/apiary.txt").unwrap();
+        assert_eq!(text.mime_type, "text/plain");
+    }
+
+    #[test]
+    fn resolve_decodes_percent_escapes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("my file.txt"), "spaced").unwrap();
+        fs::write(dir.path().join("café.txt"), "accented").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        assert_eq!(
+            server.read_file("/static/my%20file.txt").unwrap().body,
+            b"spaced"
+        );
+        assert_eq!(
+            server.resolve("/static/caf%C3%A9.txt"),
+            Some(dir.path().join("café.txt").canonicalize().unwrap())
+        );
+        assert!(matches!(
+            server.read_file("/static/my%2Xfile.txt"),
+            Err(ServeError::InvalidPath)
+        ));
+        assert!(server.resolve("/static/%ZZ.txt").is_none());
+        assert!(server.resolve("/static/caf%C3.txt").is_none());
+        assert!(server.resolve("/static/my%20file.txt%00").is_none());
+    }
+
+    #[test]
+    fn resolve_ignores_query_strings_and_fragments() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "console.log(1)").unwrap();
+        let expected = dir.path().join("app.js").canonicalize().unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .build()
+            .unwrap();
+
+        assert_eq!(server.resolve("/static/app.js?v=3"), Some(expected.clone()));
+        assert_eq!(server.resolve("/static/app.js#top"), Some(expected));
+        assert_eq!(
+            server.read_file("/static/app.js?v=3#top").unwrap().body,
+            b"console.log(1)"
+        );
+        // an escaped question mark is part of the name
+        assert!(server.resolve("/static/app.js%3Fv=3").is_none());
+    }
+
+    #[test]
+    fn hotlink_protection_checks_the_referer_host() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "jpeg").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .allowed_referers(vec!["example.com".to_string(), "*.cdn.example".to_string()])
+            .build()
+            .unwrap();
+
+        for allowed in [
+            "https://example.com/gallery",
+            "http://EXAMPLE.com:8080/",
+            "https://img.cdn.example/page?x=1",
+            "https://cdn.example",
+        ] {
+            assert!(
+                server
+                    .read_file_with_referer("/photo.jpg", Some(allowed))
+                    .is_ok(),
+                "{allowed}"
+            );
+        }
+        for denied in [
+            "https://evil.test/example.com",
+            "https://notexample.com/",
+            "https://example.com.evil.test/",
+            "https://example.com@evil.test/",
+            "not a url",
+        ] {
+            assert!(
+                matches!(
+                    server.read_file_with_referer("/photo.jpg", Some(denied)),
+                    Err(ServeError::Forbidden)
+                ),
+                "{denied}"
+            );
+        }
+
+        // an absent referer passes unless configured otherwise
+        assert!(server.read_file_with_referer("/photo.jpg", None).is_ok());
+        let strict = StaticServer::builder(dir.path())
+            .allowed_referers(vec!["example.com".to_string()])
+            .allow_missing_referer(false)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            strict.read_file_with_referer("/photo.jpg", None),
+            Err(ServeError::Forbidden)
+        ));
+    }
+
+    #[test]
+    fn reads_files_from_route_captures() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("css")).unwrap();
+        fs::write(dir.path().join("css/site.css"), "body{}").unwrap();
+        fs::write(dir.path().join("secret.txt"), "hidden").unwrap();
+        let root = dir.path().join("css");
+
+        let server = StaticServer::builder(&root)
+            .mount_path("/static")
+            .build()
+            .unwrap();
+        assert_eq!(server.read_captured("site.css").unwrap().body, b"body{}");
+        assert_eq!(server.read_captured("/site.css").unwrap().body, b"body{}");
+        // the capture isn't stripped again, even when it starts like the mount path
+        assert!(matches!(
+            server.read_captured("static/site.css"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            server.read_captured("../secret.txt"),
+            Err(ServeError::NotFound)
+        ));
+
+        let at_root = StaticServer::builder(&root).build().unwrap();
+        assert_eq!(at_root.read_captured("site.css").unwrap().body, b"body{}");
+    }
+
+    #[test]
+    fn clean_urls_serve_html_files_without_extension() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("guide.html"), "guide").unwrap();
+        fs::write(dir.path().join("about.html"), "about page").unwrap();
+        fs::create_dir(dir.path().join("about")).unwrap();
+        fs::write(dir.path().join("about/index.html"), "about index").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .serve_index(true)
+            .clean_urls(true)
+            .build()
+            .unwrap();
+
+        let guide = server.read_file("/static/guide").unwrap();
+        assert_eq!(guide.body, b"guide");
+        assert_eq!(guide.mime_type, "text/html");
+
+        // a real directory still resolves to its index
+        assert_eq!(
+            server.read_file("/static/about").unwrap().body,
+            b"about index"
+        );
+        // paths with an extension are left alone
+        assert!(server.read_file("/static/guide.txt").is_err());
+
+        let off = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .build()
+            .unwrap();
+        assert!(matches!(
+            off.read_file("/static/guide"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn try_files_replicates_the_spa_pattern() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "app shell").unwrap();
+        fs::write(dir.path().join("app.js"), "js").unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/index.html"), "docs").unwrap();
+
+        // try_files $uri $uri/ /index.html
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            try_files: vec![
+                "$uri".to_string(),
+                "$uri/".to_string(),
+                "/index.html".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        assert_eq!(server.read_file("/static/app.js").unwrap().body, b"js");
+        assert_eq!(server.read_file("/static/docs").unwrap().body, b"docs");
+        assert_eq!(
+            server.read_file("/static/users/42").unwrap().body,
+            b"app shell"
+        );
+        // the chain still can't be used to escape the root
+        assert_eq!(
+            server.read_file("/static/../etc/passwd").unwrap().body,
+            b"app shell"
+        );
+    }
+
+    #[test]
+    fn mount_path_ends_on_a_segment_boundary() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "home").unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("hack.txt"), "hack").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .build()
+            .unwrap();
+
+        assert!(server.matches_mount("/static"));
+        assert!(server.matches_mount("/static/"));
+        assert_eq!(server.read_file("/static/a.txt").unwrap().body, b"a");
+        assert!(!server.matches_mount("/statichack.txt"));
+        assert!(!server.matches_mount("/staticfiles/a.txt"));
+        assert!(server.resolve("/statichack.txt").is_none());
+        assert!(matches!(
+            server.resolve_detailed("/statichack.txt"),
+            Err(ResolveError::MountMismatch)
+        ));
+
+        // the root mount still claims every path
+        let root = StaticServer::builder(dir.path()).build().unwrap();
+        assert!(root.matches_mount("/"));
+        assert_eq!(root.read_file("/hack.txt").unwrap().body, b"hack");
+    }
+
+    #[test]
+    fn detailed_resolver_distinguishes_mount_mismatch() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("hello.txt"), "Hello").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        assert!(server.resolve_detailed("/static/hello.txt").is_ok());
+        assert!(matches!(
+            server.resolve_detailed("/assets/hello.txt"),
+            Err(ResolveError::MountMismatch)
+        ));
+        assert!(matches!(
+            server.resolve_detailed("/static/missing.txt"),
+            Err(ResolveError::Serve(ServeError::NotFound))
+        ));
+    }
+
+    #[test]
+    fn writes_exactly_the_requested_range() {
+        let dir = tempdir().unwrap();
+        let content: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+        fs::write(dir.path().join("video.mp4"), &content).unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        let mut written = Vec::new();
+        let meta = server
+            .write_range_to("/static/video.mp4", "bytes=4000-5999", &mut written)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((meta.start, meta.end, meta.size), (4000, 5999, 10_000));
+        assert_eq!(written.len() as u64, meta.len());
+        assert_eq!(written, &content[4000..6000]);
+        assert_eq!(meta.content_range(), "bytes 4000-5999/10000");
+        assert_eq!(meta.mime_type, "video/mp4");
+
+        let mut ignored = Vec::new();
+        assert!(matches!(
+            server.write_range_to("/static/video.mp4", "bytes=20000-", &mut ignored),
+            Err(ServeError::RangeNotSatisfiable { size: 10_000 })
+        ));
+        assert!(ignored.is_empty());
+        // several ranges are ignored rather than refused
+        assert_eq!(
+            server
+                .write_range_to("/static/video.mp4", "bytes=0-1,4-5", &mut ignored)
+                .unwrap(),
+            None
+        );
+        assert!(ignored.is_empty());
+    }
+
+    #[test]
+    fn refuses_files_over_the_size_limit() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("backup.tar"), vec![0u8; 4_096]).unwrap();
+
+        let strict = StaticServer::builder(dir.path())
+            .max_file_size(1_024)
+            .build()
+            .unwrap();
+        let err = strict.read_file("/backup.tar").unwrap_err();
+        assert!(matches!(
+            err,
+            ServeError::TooLarge {
+                size: 4_096,
+                limit: 1_024
+            }
+        ));
+        assert_eq!(err.status_code(), 500);
+        assert!(matches!(
+            strict.read_file_head("/backup.tar"),
+            Err(ServeError::TooLarge { .. })
+        ));
+
+        let roomy = StaticServer::builder(dir.path())
+            .max_file_size(4_096)
+            .build()
+            .unwrap();
+        assert_eq!(roomy.read_file("/backup.tar").unwrap().len, 4_096);
+    }
+
+    #[test]
+    fn reads_each_range_form_into_memory() {
+        let dir = tempdir().unwrap();
+        let content: Vec<u8> = (0..=255u8).cycle().take(2_000).collect();
+        fs::write(dir.path().join("song.mp3"), &content).unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+
+        let first = server
+            .read_range("/song.mp3", "bytes=0-1023")
+            .unwrap()
+            .unwrap();
+        assert_eq!((first.meta.start, first.meta.end), (0, 1023));
+        assert_eq!(first.body, &content[..1024]);
+
+        let tail = server
+            .read_range("/song.mp3", "bytes=-500")
+            .unwrap()
+            .unwrap();
+        assert_eq!((tail.meta.start, tail.meta.end), (1500, 1999));
+        assert_eq!(tail.body, &content[1500..]);
+
+        let rest = server
+            .read_range("/song.mp3", "bytes=500-")
+            .unwrap()
+            .unwrap();
+        assert_eq!((rest.meta.start, rest.meta.size), (500, 2_000));
+        assert_eq!(rest.body, &content[500..]);
+
+        assert!(matches!(
+            server.read_range("/song.mp3", "bytes=2000-2100"),
+            Err(ServeError::RangeNotSatisfiable { size: 2_000 })
+        ));
+        // a malformed header is ignored so the full file is sent
+        assert_eq!(server.read_range("/song.mp3", "bytes=oops").unwrap(), None);
+    }
+
+    #[test]
+    fn ranges_slice_the_identity_bytes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.gz"), gzip_encode(b"let a = 1;")).unwrap();
+
+        // even where a gzip client would get a compressed body, a range is of the identity
+        // bytes
+        let server = StaticServer::builder(dir.path())
+            .compress(true)
+            .compress_min_size(0)
+            .build()
+            .unwrap();
+        let gzip = server
+            .read_file_negotiated("/app.js", Some("gzip"))
+            .unwrap();
+        assert_eq!(gzip.content_encoding.as_deref(), Some("gzip"));
+        let range = server.read_range("/app.js", "bytes=4-4").unwrap().unwrap();
+        assert_eq!(range.body, b"a");
+        assert_eq!(range.meta.content_range(), "bytes 4-4/10");
+    }
+
+    #[test]
+    fn ranges_are_logged_and_refused_for_rewritten_bodies() {
+        use std::sync::Mutex;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("notes.txt"), "some notes").unwrap();
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink_records = Arc::clone(&records);
+        let server = StaticServer::builder(dir.path())
+            .access_log(move |record: &AccessLogRecord| {
+                sink_records.lock().unwrap().push(record.clone())
+            })
+            .build()
+            .unwrap();
+
+        let range = server
+            .read_range("/notes.txt", "bytes=0-3")
+            .unwrap()
+            .unwrap();
+        assert_eq!(range.body, b"some");
+        assert!(server.read_range("/notes.txt", "bytes=50-").is_err());
+
+        // a transformed body is only served whole
+        let transformed = StaticServer::builder(dir.path())
+            .transform(|_, _, body| body.to_ascii_uppercase())
+            .build()
+            .unwrap();
+        assert_eq!(
+            transformed.read_range("/app.js", "bytes=0-3").unwrap(),
+            None
+        );
+
+        let records = records.lock().unwrap();
+        let logged: Vec<(u16, u64)> = records
+            .iter()
+            .map(|record| (record.status, record.bytes))
+            .collect();
+        assert_eq!(logged, [(206, 4), (416, 0)]);
+        assert_eq!(records[0].mime_type.as_deref(), Some("text/plain"));
+    }
+
+    #[test]
+    fn read_first_of_serves_the_first_existing_path() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("img")).unwrap();
+        fs::write(dir.path().join("img/logo.png"), "png").unwrap();
+        fs::write(dir.path().join("img/.secret.svg"), "svg").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .build()
+            .unwrap();
+        let logo = server
+            .read_first_of(&["/static/img/logo.svg", "/static/img/logo.png"])
+            .unwrap();
+        assert_eq!(logo.body, b"png");
+        assert_eq!(logo.mime_type, "image/png");
+
+        assert!(matches!(
+            server.read_first_of(&[
+                "/static/img/a.svg",
+                "/static/../img/logo.png",
+                "/img/logo.png"
+            ]),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            server.read_first_of(&[]),
+            Err(ServeError::NotFound)
+        ));
+        // a refusal ends the chain rather than being skipped over
+        assert!(matches!(
+            server.read_first_of(&["/static/img/.secret.svg", "/static/img/logo.png"]),
+            Err(ServeError::Forbidden)
+        ));
+    }
+
+    #[test]
+    fn ranges_of_an_empty_file_are_unsatisfiable() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("empty.bin"), "").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        for range in ["bytes=0-0", "bytes=0-", "bytes=-1"] {
+            let err = server.read_range("/empty.bin", range).unwrap_err();
+            assert!(matches!(err, ServeError::RangeNotSatisfiable { size: 0 }));
+            assert_eq!(err.status_code(), 416);
+            assert_eq!(err.content_range().as_deref(), Some("bytes */0"));
+        }
+        assert_eq!(ServeError::NotFound.content_range(), None);
+    }
+
+    #[test]
+    fn preconnect_links_go_on_html_responses() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        fs::write(dir.path().join("app.js"), "ok").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .preconnect_origins(vec![
+                "https://cdn.example.com".to_string(),
+                "https://analytics.example.com:8443".to_string(),
+            ])
+            .build()
+            .unwrap();
+
+        let page = server.read_file("/index.html").unwrap();
+        let link = "<https://cdn.example.com>; rel=preconnect, \
+                    <https://analytics.example.com:8443>; rel=preconnect";
+        assert!(page.headers.contains(&("Link", link.to_string())));
+        let script = server.read_file("/app.js").unwrap();
+        assert!(script.headers.iter().all(|(name, _)| *name != "Link"));
+
+        let invalid = [
+            "cdn.example.com",
+            "https://",
+            "https://cdn.example.com/lib.js",
+            "https://cdn.example.com:",
+            "https://cdn.example.com>; rel=preload",
+        ];
+        for origin in invalid {
+            let built = StaticServer::builder(dir.path())
+                .preconnect_origins(vec![origin.to_string()])
+                .build();
+            assert_eq!(
+                built.err(),
+                Some(ConfigError::InvalidPreconnectOrigin(origin.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn cache_control_reaches_the_file_and_hashed_names_are_immutable() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "plain").unwrap();
+        fs::write(dir.path().join("app.abc123.js"), "hashed").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .cache_control("public, max-age=60")
+            .immutable_for_hashed(true)
+            .build()
+            .unwrap();
+        assert_eq!(server.cache_control(), Some("public, max-age=60"));
+
+        let plain = server.read_file("/app.js").unwrap();
+        assert_eq!(plain.cache_control(), Some("public, max-age=60"));
+        let hashed = server.read_file("/app.abc123.js").unwrap();
+        assert_eq!(hashed.cache_control(), Some(IMMUTABLE_CACHE_CONTROL));
+        assert_eq!(
+            hashed
+                .headers
+                .iter()
+                .filter(|(name, _)| *name == "Cache-Control")
+                .count(),
+            1
+        );
+
+        assert!(has_content_hash("index-B4x9kQ2z.css"));
+        assert!(!has_content_hash("jquery-3.6.0.min.js"));
+        assert!(!has_content_hash("bootstrap.bundle.min.js"));
+        assert!(has_content_hash("main.3f2a9c1e.js"));
+        // dates and timestamps are all digits, not hashes
+        assert!(!has_content_hash("report.20240101.pdf"));
+        assert!(!has_content_hash("photo-1700000000.jpg"));
+        assert!(!has_content_hash("app.v2x1.js"));
+    }
+
+    #[test]
+    fn diffs_two_roots_by_content() {
+        let (old, new) = (tempdir().unwrap(), tempdir().unwrap());
+        for root in [old.path(), new.path()] {
+            fs::create_dir(root.join("css")).unwrap();
+            fs::write(root.join("css/site.css"), "body{}").unwrap();
+        }
+        fs::write(old.path().join("app.js"), "v1").unwrap();
+        fs::write(new.path().join("app.js"), "v2").unwrap();
+        fs::write(old.path().join("legacy.js"), "old").unwrap();
+        fs::write(new.path().join("css/print.css"), "@media print{}").unwrap();
+        fs::write(new.path().join(".env"), "SECRET=1").unwrap();
+
+        let server = StaticServer::builder(new.path())
+            .serve_hidden(false)
+            .build()
+            .unwrap();
+        assert_eq!(
+            server.diff_roots(old.path(), new.path()),
+            RootDiff {
+                added: vec!["css/print.css".to_string()],
+                removed: vec!["legacy.js".to_string()],
+                modified: vec!["app.js".to_string()],
+            }
+        );
+        assert!(server.diff_roots(new.path(), new.path()).is_empty());
+    }
+
+    #[test]
+    fn lists_directories_as_json() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("assets/img")).unwrap();
+        fs::write(dir.path().join("assets/b.css"), "body{}").unwrap();
+        fs::write(dir.path().join("assets/a.js"), "ok").unwrap();
+        fs::write(dir.path().join("assets/.env"), "SECRET=1").unwrap();
+        let stamp = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for name in ["a.js", "b.css"] {
+            File::options()
+                .write(true)
+                .open(dir.path().join("assets").join(name))
+                .unwrap()
+                .set_modified(stamp)
+                .unwrap();
+        }
+
+        let server = StaticServer::builder(dir.path())
+            .serve_hidden(false)
+            .build()
+            .unwrap();
+        let listing = server.list_directory_json("/assets").unwrap();
+        assert_eq!(listing.mime_type, "application/json");
+
+        let img_modified = fs::metadata(dir.path().join("assets/img"))
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let expected = format!(
+            "[{},{},{}]",
+            r#"{"name":"a.js","is_dir":false,"file_type":"file","target":null,"size":2,"modified":1700000000}"#,
+            r#"{"name":"b.css","is_dir":false,"file_type":"file","target":null,"size":6,"modified":1700000000}"#,
+            format_args!(
+                r#"{{"name":"img","is_dir":true,"file_type":"dir","target":null,"size":0,"modified":{}}}"#,
+                img_modified
+            )
+        );
+        assert_eq!(String::from_utf8(listing.body).unwrap(), expected);
+
+        assert!(matches!(
+            server.list_directory_json("/../"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(server.list_directory_json("/assets/a.js").is_err());
+    }
+
+    #[test]
+    fn lists_directories_without_an_index() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs/guides")).unwrap();
+        fs::write(dir.path().join("docs/b.txt"), "b").unwrap();
+        fs::write(dir.path().join("docs/a <script>.txt"), "a").unwrap();
+        fs::write(dir.path().join("docs/.env"), "SECRET=1").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .directory_listing(true)
+            .serve_hidden(false)
+            .build()
+            .unwrap();
+
+        let listing = server.read_file("/static/docs/").unwrap();
+        assert_eq!(listing.mime_type, "text/html; charset=utf-8");
+        let html = String::from_utf8(listing.body).unwrap();
+        assert!(html.contains(r#"<a href="/static/">../</a>"#));
+        assert!(html.contains(r#"<a href="/static/docs/guides/">guides/</a>"#));
+        assert!(
+            html.contains(
+                r#"<a href="/static/docs/a%20%3Cscript%3E.txt">a &lt;script&gt;.txt</a>"#
+            )
+        );
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains(".env"));
+
+        // directories first, then files by name
+        let guides = html.find("guides/").unwrap();
+        let a = html.find("a &lt;script").unwrap();
+        let b = html.find(">b.txt<").unwrap();
+        assert!(guides < a && a < b);
+
+        // files are still served, and traversal is refused
+        assert_eq!(server.read_file("/static/docs/b.txt").unwrap().body, b"b");
+        assert!(server.list_directory("/static/../").is_err());
+
+        let off = StaticServer::builder(dir.path()).build().unwrap();
+        assert!(matches!(off.read_file("/docs/"), Err(ServeError::NotFound)));
+    }
+
+    #[test]
+    fn sorted_listing_puts_directories_first_then_names_case_sensitively() {
+        let dir = tempdir().unwrap();
+        for name in ["zeta", "Alpha", "beta"] {
+            fs::create_dir_all(dir.path().join("docs").join(name)).unwrap();
+        }
+        for name in ["b.txt", "B.txt", "a.txt", "_notes.md", "10.txt", "2.txt"] {
+            fs::write(dir.path().join("docs").join(name), name).unwrap();
+        }
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let names: Vec<String> = server
+            .list_directory_sorted("/docs")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Alpha",
+                "beta",
+                "zeta",
+                "10.txt",
+                "2.txt",
+                "B.txt",
+                "_notes.md",
+                "a.txt",
+                "b.txt"
+            ]
+        );
+
+        assert!(matches!(
+            server.list_directory_sorted("/missing"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listings_report_symlinks_and_their_targets() {
+        let dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs/releases")).unwrap();
+        fs::write(dir.path().join("docs/releases/v1.txt"), "v1").unwrap();
+        fs::write(dir.path().join("docs/notes.txt"), "notes").unwrap();
+        fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink("releases/v1.txt", dir.path().join("docs/latest.txt")).unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.txt"),
+            dir.path().join("docs/escape.txt"),
+        )
+        .unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .follow_symlinks(true)
+            .build()
+            .unwrap();
+        let (_, _, entries) = server.directory_entries("/static/docs").unwrap();
+        let latest = entries
+            .iter()
+            .find(|entry| entry.name == "latest.txt")
+            .unwrap();
+        assert_eq!(latest.file_type, FileType::Symlink);
+        assert_eq!(latest.target.as_deref(), Some("docs/releases/v1.txt"));
+        assert_eq!(latest.size, 2);
+        let notes = entries
+            .iter()
+            .find(|entry| entry.name == "notes.txt")
+            .unwrap();
+        assert_eq!(notes.file_type, FileType::File);
+        assert_eq!(notes.target, None);
+        let releases = entries
+            .iter()
+            .find(|entry| entry.name == "releases")
+            .unwrap();
+        assert_eq!(releases.file_type, FileType::Dir);
+        // a link leaving the root is not listed at all
+        assert!(!entries.iter().any(|entry| entry.name == "escape.txt"));
+
+        let html = String::from_utf8(server.list_directory("/static/docs/").unwrap().body).unwrap();
+        assert!(html.contains(
+            r#"<li class="symlink"><a href="/static/docs/latest.txt">latest.txt</a> &rarr; /static/docs/releases/v1.txt</li>"#
+        ));
+        assert!(html.contains(r#"<li><a href="/static/docs/notes.txt">notes.txt</a></li>"#));
+
+        // without following symlinks the link is neither served nor listed
+        let strict = StaticServer::builder(dir.path())
+            .follow_symlinks(false)
+            .build()
+            .unwrap();
+        let (_, _, entries) = strict.directory_entries("/docs").unwrap();
+        assert!(!entries.iter().any(|entry| entry.name == "latest.txt"));
+    }
+
+    #[test]
+    fn decompresses_lone_gzip_files_within_the_limit() {
+        let dir = tempdir().unwrap();
+        let text = "console.log('hi');\n".repeat(50);
+        fs::write(dir.path().join("app.js.gz"), gzip_encode(text.as_bytes())).unwrap();
+        fs::write(
+            dir.path().join("bomb.txt.gz"),
+            gzip_encode(&vec![b'a'; 1 << 20]),
+        )
+        .unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .max_decompressed_size(64 * 1024)
+            .build()
+            .unwrap();
+
+        let plain = server.read_file("/app.js").unwrap();
+        assert_eq!(plain.body, text.as_bytes());
+        assert_eq!(plain.mime_type, "text/javascript");
+        assert_eq!(plain.content_encoding, None);
+
+        let gzip = server
+            .read_file_negotiated("/app.js", Some("gzip"))
+            .unwrap();
+        assert_eq!(gzip.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(gzip_decode(&gzip.body, None).unwrap(), text.as_bytes());
+
+        assert!(matches!(
+            server.read_file("/bomb.txt"),
+            Err(ServeError::TooLarge {
+                size: 1_048_576,
+                limit: 65_536
+            })
+        ));
+        // clients which take the compressed bytes are never limited
+        assert!(
+            server
+                .read_file_negotiated("/bomb.txt", Some("gzip"))
+                .is_ok()
+        );
+        assert!(matches!(
+            server.read_file("/missing.js"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn lone_gzip_files_are_opt_in() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js.gz"), gzip_encode(b"let a = 1;")).unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        assert!(matches!(
+            server.read_file_negotiated("/app.js", Some("gzip")),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            server.read_file("/app.js"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn lone_gzip_files_follow_the_identity_file_policy() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js.gz"), gzip_encode(b"let a = 1;")).unwrap();
+        fs::write(dir.path().join("app.js.map.gz"), gzip_encode(b"{}")).unwrap();
+        fs::write(dir.path().join(".env.gz"), gzip_encode(b"SECRET=1")).unwrap();
+        let set = |extensions: &[&str]| extensions.iter().map(|e| e.to_string()).collect();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .denied_extensions(set(&["map"]))
+            .build()
+            .unwrap();
+        assert_eq!(server.read_file("/app.js").unwrap().body, b"let a = 1;");
+        for path in ["/app.js.map", "/.env"] {
+            assert!(server.read_file_negotiated(path, Some("gzip")).is_err());
+            assert!(server.read_file(path).is_err());
+        }
+
+        let allowlisted = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .allowed_extensions(set(&["js"]))
+            .build()
+            .unwrap();
+        assert_eq!(
+            allowlisted.read_file("/app.js").unwrap().body,
+            b"let a = 1;"
+        );
+    }
+
+    #[test]
+    fn lone_gzip_files_are_limited_by_default() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("bomb.txt.gz"),
+            gzip_encode(&vec![b'a'; DEFAULT_MAX_DECOMPRESSED_SIZE as usize + 1]),
+        )
+        .unwrap();
+        fs::write(dir.path().join("big.txt.gz"), gzip_encode(&[b'a'; 4096])).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            server.read_file("/bomb.txt"),
+            Err(ServeError::TooLarge {
+                limit: DEFAULT_MAX_DECOMPRESSED_SIZE,
+                ..
+            })
+        ));
+
+        // max_file_size stands in when no decompression limit is set
+        let capped = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .max_file_size(1024)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            capped.read_file("/big.txt"),
+            Err(ServeError::TooLarge { limit: 1024, .. })
+        ));
+    }
+
+    #[test]
+    fn lone_gzip_files_are_transformed() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js.gz"), gzip_encode(b"let a = 1;")).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .transform(|_, _, body| body.to_ascii_uppercase())
+            .build()
+            .unwrap();
+        let plain = server.read_file("/app.js").unwrap();
+        assert_eq!(plain.body, b"LET A = 1;");
+        let gzip = server
+            .read_file_negotiated("/app.js", Some("gzip"))
+            .unwrap();
+        assert_eq!(gzip.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(gzip_decode(&gzip.body, None).unwrap(), b"LET A = 1;");
+        // the weak ETag of a transformed body is the same whichever encoding is sent
+        assert!(plain.etag.as_deref().unwrap().starts_with("W/"));
+    }
+
+    #[test]
+    fn serves_not_found_file_for_misses() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("404.html"), "<h1>Lost?</h1>").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .not_found_file("404.html")
+            .build()
+            .unwrap();
+        let page = server.read_file("/missing.js").unwrap();
+        assert_eq!(page.status, 404);
+        assert_eq!(page.body, b"<h1>Lost?</h1>");
+        assert_eq!(page.mime_type, "text/html");
+
+        // a page which doesn't exist leaves the plain error
+        let misconfigured = StaticServer::builder(dir.path())
+            .not_found_file("nope.html")
+            .build()
+            .unwrap();
+        assert!(matches!(
+            misconfigured.read_file("/missing.js"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn serves_forbidden_page_for_blocked_dotfile() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+        fs::write(dir.path().join("403.html"), "<h1>Coming soon</h1>").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_hidden: false,
+            forbidden_page: Some(PathBuf::from("403.html")),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        let page = server.read_file("/static/.env").unwrap();
+
+        assert_eq!(page.status, 403);
+        assert_eq!(page.body, b"<h1>Coming soon</h1>");
+        assert_eq!(page.mime_type, "text/html");
+
+        // without a page the plain error comes back
+        let plain = StaticServer::from_config(StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            serve_hidden: false,
+            ..Default::default()
+        });
+        assert!(matches!(
+            plain.read_file("/static/.env"),
+            Err(ServeError::Forbidden)
+        ));
+    }
+
+    #[test]
+    fn hidden_paths_are_blocked_by_default() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("secret/.hidden")).unwrap();
+        fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+        fs::write(dir.path().join("secret/.hidden/file"), "hidden").unwrap();
+        fs::write(dir.path().join("secret/visible.txt"), "visible").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        assert!(!server.serve_hidden());
+        for path in ["/.env", "/secret/.hidden/file", "/secret/%2Ehidden/file"] {
+            assert!(server.resolve(path).is_none(), "{path}");
+            assert!(matches!(server.read_file(path), Err(ServeError::Forbidden)));
+        }
+        assert_eq!(
+            server.read_file("/secret/visible.txt").unwrap().body,
+            b"visible"
+        );
+
+        let open = StaticServer::builder(dir.path())
+            .serve_hidden(true)
+            .build()
+            .unwrap();
+        assert_eq!(open.read_file("/.env").unwrap().body, b"SECRET=1");
+        assert_eq!(
+            open.read_file("/secret/.hidden/file").unwrap().body,
+            b"hidden"
+        );
+    }
+
+    #[test]
+    fn build_id_etags_agree_across_servers() {
+        let replica_a = tempdir().unwrap();
+        let replica_b = tempdir().unwrap();
+        for root in [replica_a.path(), replica_b.path()] {
+            fs::write(root.join("app.js"), "console.log(1)").unwrap();
+            fs::write(root.join("app.css"), "body {}").unwrap();
+        }
+
+        let server_for = |root: &Path| {
+            StaticServer::from_config(StaticServerConfig {
+                mount_path: "/static".into(),
+                root_dir: root.to_path_buf(),
+                build_id: Some("build-42".to_string()),
+                ..Default::default()
+            })
+        };
+        let a = server_for(replica_a.path());
+        let b = server_for(replica_b.path());
+
+        let etag_a = a.read_file("/static/app.js").unwrap().etag.unwrap();
+        let etag_b = b.read_file("/static/app.js").unwrap().etag.unwrap();
+        assert_eq!(etag_a, etag_b);
+        assert!(etag_a.starts_with("\"build-42-") && etag_a.ends_with('"'));
+
+        let other = a.read_file("/static/app.css").unwrap().etag.unwrap();
+        assert_ne!(etag_a, other);
+    }
+
+    #[test]
+    fn freshness_follows_validator_precedence() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "console.log(1)").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+        let etag = server.read_file("/static/app.js").unwrap().etag.unwrap();
+        let modified = fs::metadata(dir.path().join("app.js"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        let later = modified + std::time::Duration::from_secs(60);
+        let earlier = modified - std::time::Duration::from_secs(60);
+
+        // ETag only
+        assert!(
+            server
+                .is_fresh("/static/app.js", Some(&etag), None)
+                .unwrap()
+        );
+        assert!(
+            !server
+                .is_fresh("/static/app.js", Some("\"stale\""), None)
+                .unwrap()
+        );
+
+        // date only
+        assert!(
+            server
+                .is_fresh("/static/app.js", None, Some(later))
+                .unwrap()
+        );
+        assert!(
+            !server
+                .is_fresh("/static/app.js", None, Some(earlier))
+                .unwrap()
+        );
+
+        // both present: the ETag decides
+        assert!(
+            server
+                .is_fresh("/static/app.js", Some(&etag), Some(earlier))
+                .unwrap()
+        );
+
+        // neither
+        assert!(!server.is_fresh("/static/app.js", None, None).unwrap());
+
+        assert!(matches!(
+            server.is_fresh("/static/missing.js", Some(&etag), None),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn stat_reports_metadata_without_reading_the_file() {
+        use crate::vfs::mock::CountingFs;
+
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("app.js"), "console.log('hi');").unwrap();
+
+        let fs_calls = Arc::new(CountingFs::default());
+        let server = StaticServer::builder(dir.path())
+            .vfs(fs_calls.clone())
+            .build()
+            .unwrap();
+        let meta = server.stat("/app.js").unwrap();
+        let on_disk = fs::metadata(dir.path().join("app.js")).unwrap();
+        assert_eq!(meta.size, on_disk.len());
+        assert_eq!(meta.mime_type, "text/javascript");
+        assert_eq!(meta.modified, on_disk.modified().ok());
+        assert_eq!(fs_calls.count_for("open", "app.js"), 0);
+        assert_eq!(fs_calls.count_for("read", "app.js"), 0);
+
+        assert!(matches!(
+            server.stat("/missing.js"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(server.stat("/docs"), Err(ServeError::NotFound)));
+        assert!(server.stat("/../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn fixed_last_modified_overrides_file_mtimes() {
+        let first = tempdir().unwrap();
+        let second = tempdir().unwrap();
+        for (dir, secs) in [(&first, 1_000_000), (&second, 2_000_000)] {
+            fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+            File::options()
+                .write(true)
+                .open(dir.path().join("app.js"))
+                .unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        }
+
+        let build_time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let serve = |dir: &Path| {
+            StaticServer::builder(dir)
+                .fixed_last_modified(build_time)
+                .build()
+                .unwrap()
+        };
+        let (a, b) = (serve(first.path()), serve(second.path()));
+        let (file_a, file_b) = (
+            a.read_file("/app.js").unwrap(),
+            b.read_file("/app.js").unwrap(),
+        );
+        assert_eq!(file_a.modified, Some(build_time));
+        assert_eq!(file_a.etag, file_b.etag);
+        assert_eq!(
+            a.read_file_head("/app.js").unwrap().modified,
+            Some(build_time)
+        );
+        assert_eq!(a.stat("/app.js").unwrap().modified, Some(build_time));
+        assert!(a.is_fresh("/app.js", None, Some(build_time)).unwrap());
+
+        let unfixed = StaticServer::builder(first.path()).build().unwrap();
+        assert_eq!(
+            unfixed.read_file("/app.js").unwrap().modified,
+            Some(UNIX_EPOCH + Duration::from_secs(1_000_000))
+        );
+    }
+
+    #[test]
+    fn index_candidates_stop_at_the_first_match_and_the_cap() {
+        use crate::vfs::mock::CountingFs;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.htm"), "htm").unwrap();
+        fs::write(dir.path().join("default.html"), "default").unwrap();
+        let candidates = ["index.html", "index.htm", "default.html", "home.html"];
+
+        let fs_calls = Arc::new(CountingFs::default());
+        let server = StaticServer::builder(dir.path())
+            .serve_index(true)
+            .index_files(candidates.iter().map(|name| name.to_string()).collect())
+            .vfs(fs_calls.clone())
+            .build()
+            .unwrap();
+        assert_eq!(server.read_file("/").unwrap().body, b"htm");
+        assert_eq!(fs_calls.count_for("metadata", "index.html"), 1);
+        assert_eq!(fs_calls.count_for("metadata", "default.html"), 0);
+        assert_eq!(fs_calls.count_for("metadata", "home.html"), 0);
+
+        // names past the cap are never statted, even when none before them exist
+        let fs_calls = Arc::new(CountingFs::default());
+        let server = StaticServer::builder(dir.path())
+            .serve_index(true)
+            .index_files(vec!["index.html".to_string(), "default.html".to_string()])
+            .max_index_candidates(1)
+            .vfs(fs_calls.clone())
+            .build()
+            .unwrap();
+        assert!(matches!(server.read_file("/"), Err(ServeError::NotFound)));
+        assert_eq!(fs_calls.count_for("metadata", "default.html"), 0);
+    }
+
+    #[test]
+    fn negative_cache_skips_the_filesystem_for_repeated_misses() {
+        use crate::vfs::mock::CountingFs;
+
+        let dir = tempdir().unwrap();
+        let fs_calls = Arc::new(CountingFs::default());
+        let server = StaticServer::builder(dir.path())
+            .negative_cache_ttl(Duration::from_secs(60))
+            .vfs(fs_calls.clone())
+            .build()
+            .unwrap();
+
+        for _ in 0..3 {
+            assert!(matches!(
+                server.read_file("/missing.txt"),
+                Err(ServeError::NotFound)
+            ));
+        }
+        assert_eq!(fs_calls.count_for("canonicalize", "missing.txt"), 1);
+        // lone `.gz` files aren't served by default, so none is looked for
+        assert_eq!(fs_calls.count_for("canonicalize", "missing.txt.gz"), 0);
+        assert_eq!(
+            server.negative_cache_stats(),
+            Some(NegativeCacheStats {
+                hits: 2,
+                misses: 1,
+                entries: 1
+            })
+        );
+
+        // once the file appears, invalidating the path makes it servable right away
+        fs::write(dir.path().join("missing.txt"), "here now").unwrap();
+        assert!(server.read_file("/missing.txt").is_err());
+        assert!(server.invalidate("/missing.txt"));
+        assert_eq!(server.read_file("/missing.txt").unwrap().body, b"here now");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cache_skips_rewalking_the_chain() {
+        use crate::vfs::mock::CountingFs;
+
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("releases/v1")).unwrap();
+        fs::write(dir.path().join("releases/v1/app.js"), "v1").unwrap();
+        std::os::unix::fs::symlink("releases/v1", dir.path().join("current")).unwrap();
+
+        let outside = tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), "nope").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("escape")).unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            symlink_cache_ttl: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+
+        let fs_calls = Arc::new(CountingFs::default());
+        let mut server = StaticServer::from_config(config);
+        server.set_vfs(fs_calls.clone());
+
+        for _ in 0..3 {
+            assert_eq!(
+                server.read_file("/static/current/app.js").unwrap().body,
+                b"v1"
+            );
+        }
+        assert_eq!(fs_calls.count_for("canonicalize", "current/app.js"), 1);
+
+        // cached or not, an escaping symlink is rejected on every hit
+        for _ in 0..2 {
+            assert!(matches!(
+                server.read_file("/static/escape/secret.txt"),
+                Err(ServeError::NotFound)
+            ));
+        }
+
+        server.clear_symlink_cache();
+        server.read_file("/static/current/app.js").unwrap();
+        assert_eq!(fs_calls.count_for("canonicalize", "current/app.js"), 2);
+    }
+
+    #[test]
+    fn serves_acme_challenge_tokens_from_their_own_directory() {
+        let root = tempdir().unwrap();
+        let challenges = tempdir().unwrap();
+        fs::write(
+            challenges.path().join("tok_EN-123"),
+            "tok_EN-123.thumbprint",
+        )
+        .unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: root.path().to_path_buf(),
+            serve_hidden: false,
+            acme_challenge_dir: Some(challenges.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        let token = server
+            .read_file("/.well-known/acme-challenge/tok_EN-123")
+            .unwrap();
+        assert_eq!(token.body, b"tok_EN-123.thumbprint");
+        assert_eq!(token.mime_type, "text/plain");
+        assert!(
+            token
+                .headers
+                .contains(&("Cache-Control", "no-store".to_string()))
+        );
+
+        assert!(matches!(
+            server.read_file("/.well-known/acme-challenge/missing"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            server.read_file("/.well-known/acme-challenge/..%2Fsecret"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn rejects_drive_letters_and_streams() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("hello.txt"), "Hello").unwrap();
+
+        let config = StaticServerConfig {
+            mount_path: "/static".into(),
+            root_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let server = StaticServer::from_config(config);
+
+        for path in [
+            "/static/C:/Windows/win.ini",
+            "/static/hello.txt:$DATA",
+            "/static/C%3A/Windows/win.ini",
+        ] {
+            assert!(matches!(
+                server.read_file(path),
+                Err(ServeError::InvalidPath)
+            ));
+        }
+        assert!(server.read_file("/static/hello.txt").is_ok());
+    }
+
+    #[test]
+    fn caches_gzip_and_identity_variants_side_by_side() {
+        let dir = tempdir().unwrap();
+        let text = "body { color: red; }\n".repeat(100);
+        fs::write(dir.path().join("site.css"), &text).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .compress(true)
+            .cache(CacheConfig::default())
+            .build()
+            .unwrap();
+
+        let gzipped = server
+            .read_file_negotiated("/site.css", Some("gzip"))
+            .unwrap();
+        assert_eq!(gzipped.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(
+            crate::gzip::gzip_decode(&gzipped.body, None).unwrap(),
+            text.as_bytes()
+        );
+
+        let identity = server.read_file("/site.css").unwrap();
+        assert_eq!(identity.content_encoding, None);
+        assert_eq!(identity.body, text.as_bytes());
+
+        let stats = server.cache_stats().unwrap();
+        assert_eq!(stats.entries, 2);
+
+        server
+            .read_file_negotiated("/site.css", Some("gzip"))
+            .unwrap();
+        server.read_file("/site.css").unwrap();
+        let stats = server.cache_stats().unwrap();
+        // compressing cached the identity body too, so both identity reads were hits
+        assert_eq!((stats.gzip_hits, stats.identity_hits), (1, 2));
+
+        // a rewritten file invalidates both variants
+        fs::write(dir.path().join("site.css"), "p {}").unwrap();
+        assert_eq!(server.read_file("/site.css").unwrap().body, b"p {}");
+        assert_eq!(server.cache_stats().unwrap().entries, 1);
+    }
+
+    #[test]
+    fn resolves_without_recanonicalizing_the_root() {
+        use crate::vfs::mock::CountingFs;
+
+        let parent = tempdir().unwrap();
+        let root = parent.path().join("public");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("app.js"), "ok").unwrap();
+        fs::write(parent.path().join("secret.txt"), "nope").unwrap();
+
+        let fs_calls = Arc::new(CountingFs::default());
+        let server = StaticServer::builder(&root)
+            .vfs(fs_calls.clone())
+            .build()
+            .unwrap();
+        let at_build = fs_calls.count_for("canonicalize", "public");
+
+        for _ in 0..1_000 {
+            assert_eq!(server.resolve("/app.js"), Some(root.join("app.js")));
+        }
+        assert_eq!(fs_calls.count_for("canonicalize", "public"), at_build);
+
+        // containment is still checked against the cached root
+        for path in ["/../secret.txt", "/%2e%2e/secret.txt", "/./../secret.txt"] {
+            assert_eq!(server.resolve(path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn try_from_config_checks_the_root_directory() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("file.txt"), "not a dir").unwrap();
+        let config = |root: PathBuf| StaticServerConfig {
+            root_dir: root,
+            ..Default::default()
+        };
+
+        let missing = dir.path().join("pubilc");
+        assert_eq!(
+            StaticServer::try_from_config(config(missing.clone())).err(),
+            Some(ConfigError::RootDirNotFound(missing))
+        );
+        let file = dir.path().join("file.txt");
+        assert_eq!(
+            StaticServer::try_from_config(config(file.clone())).err(),
+            Some(ConfigError::RootDirNotADirectory(file))
+        );
+
+        let server = StaticServer::try_from_config(config(dir.path().to_path_buf())).unwrap();
+        assert_eq!(server.read_file("/file.txt").unwrap().body, b"not a dir");
+        assert!(
+            StaticServer::builder(dir.path().join("pubilc"))
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn redirects_non_canonical_hosts() {
+        let dir = tempdir().unwrap();
+        let server = StaticServer::builder(dir.path())
+            .canonical_host("example.com")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            server.host_redirect("www.example.com", "/app.js?v=2"),
+            Some("https://example.com/app.js?v=2".to_string())
+        );
+        assert_eq!(server.host_redirect("Example.COM:443", "/app.js"), None);
+        assert_eq!(server.host_redirect("example.com.", "/app.js"), None);
+
+        let server = StaticServer::builder(dir.path())
+            .canonical_host("http://localhost:8080/")
+            .build()
+            .unwrap();
+        assert_eq!(
+            server.host_redirect("127.0.0.1:8080", "/"),
+            Some("http://localhost:8080/".to_string())
+        );
+
+        let unset = StaticServer::builder(dir.path()).build().unwrap();
+        assert_eq!(unset.host_redirect("www.example.com", "/"), None);
+    }
+
+    #[test]
+    fn compresses_only_large_compressible_files() {
+        let dir = tempdir().unwrap();
+        let text = "{\"key\": \"value\"}\n".repeat(200);
+        fs::write(dir.path().join("data.json"), &text).unwrap();
+        fs::write(dir.path().join("tiny.json"), "{}").unwrap();
+        fs::write(dir.path().join("photo.png"), vec![0u8; 8_192]).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .compress(true)
+            .compress_min_size(512)
+            .build()
+            .unwrap();
+        let read = |path| server.read_file_negotiated(path, Some("gzip")).unwrap();
+
+        let large = read("/data.json");
+        assert_eq!(large.content_encoding.as_deref(), Some("gzip"));
+        assert!(large.body.len() < text.len());
+
+        assert_eq!(read("/tiny.json").content_encoding, None);
+        assert_eq!(read("/photo.png").content_encoding, None);
+        assert_eq!(
+            server.available_encodings("/photo.png"),
+            vec![Encoding::Identity]
+        );
+    }
+
+    #[test]
+    fn content_location_names_the_served_index() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/index.html"), "<h1>Docs</h1>").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .serve_index(true)
+            .content_location(true)
+            .build()
+            .unwrap();
+
+        let index = server.read_file("/static/docs/").unwrap();
+        assert!(
+            index
+                .headers
+                .contains(&("Content-Location", "/static/docs/index.html".to_string()))
+        );
+
+        let direct = server.read_file("/static/docs/index.html").unwrap();
+        assert!(
+            direct
+                .headers
+                .iter()
+                .all(|(name, _)| *name != "Content-Location")
+        );
+    }
+
+    #[test]
+    fn newest_modified_index_wins_when_selected() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "old").unwrap();
+        fs::write(dir.path().join("index.htm"), "new").unwrap();
+        let older = SystemTime::now() - std::time::Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(dir.path().join("index.html"))
+            .unwrap()
+            .set_modified(older)
+            .unwrap();
+
+        let builder = || {
+            StaticServer::builder(dir.path())
+                .serve_index(true)
+                .index_files(vec!["index.html".to_string(), "index.htm".to_string()])
+        };
+
+        let first_match = builder().build().unwrap();
+        assert_eq!(first_match.read_file("/").unwrap().body, b"old");
+
+        let newest = builder()
+            .index_selection(IndexSelection::NewestModified)
+            .build()
+            .unwrap();
+        assert_eq!(newest.read_file("/").unwrap().body, b"new");
+    }
+
+    #[test]
+    fn canonical_path_includes_the_index() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/index.html"), "docs").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .serve_index(true)
+            .build()
+            .unwrap();
+
+        let expected = fs::canonicalize(dir.path().join("docs/index.html")).unwrap();
+        assert_eq!(server.canonical_path("/static/docs/").unwrap(), expected);
+        assert!(matches!(
+            server.canonical_path("/static/missing"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn straight_file_streams_with_content_length() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("video.bin"), vec![7u8; 10_000]).unwrap();
+        fs::write(dir.path().join("index.html"), "<head></head>").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_index(true)
+            .inject_base_href(true)
+            .build()
+            .unwrap();
+
+        let mut stream = server.read_file_stream("/video.bin").unwrap();
+        assert_eq!(stream.mode, StreamMode::ContentLength);
+        assert_eq!(stream.len, Some(10_000));
+        let mut body = Vec::new();
+        stream.read_to_end(&mut body).unwrap();
+        assert_eq!(body, vec![7u8; 10_000]);
+
+        let index = server.read_file_stream("/").unwrap();
+        assert_eq!(index.mode, StreamMode::Chunked);
+        assert_eq!(index.len, None);
+    }
+
+    #[test]
+    fn streams_a_file_in_chunks_with_its_metadata() {
+        let dir = tempdir().unwrap();
+        let content: Vec<u8> = (0..=255u8).cycle().take(20_000).collect();
+        fs::write(dir.path().join("archive.zip"), &content).unwrap();
+        let modified = fs::metadata(dir.path().join("archive.zip"))
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let mut stream = server.read_file_stream("/archive.zip").unwrap();
+        assert_eq!(stream.mime_type, "application/zip");
+        assert_eq!(stream.modified, Some(modified));
+
+        let mut received = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let read = stream.read(&mut chunk).unwrap();
+            if read == 0 {
+                break;
+            }
+            received.extend_from_slice(&chunk[..read]);
+        }
+        assert_eq!(stream.len, Some(received.len() as u64));
+        assert_eq!(received, content);
+    }
+
+    #[test]
+    fn open_file_returns_a_readable_handle() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("data.json"), r#"{"ok":true}"#).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .build()
+            .unwrap();
+
+        let (mut file, meta) = server.open_file("/static/data.json").unwrap();
+        let mut body = Vec::new();
+        file.read_to_end(&mut body).unwrap();
+        assert_eq!(body, br#"{"ok":true}"#);
+        assert_eq!(meta.len, body.len() as u64);
+        assert_eq!(meta.mime_type, "application/json");
+
+        assert!(matches!(
+            server.open_file("/static/../data.json"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn limits_simultaneously_open_streams() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("video.mp4"), "frames").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .max_open_streams(2)
+            .build()
+            .unwrap();
+
+        let first = server.read_file_stream("/video.mp4").unwrap();
+        let (_file, meta) = server.open_file("/video.mp4").unwrap();
+        let copy = meta.clone();
+        assert_eq!(server.open_streams(), 2);
+
+        let err = server.read_file_stream("/video.mp4").unwrap_err();
+        assert!(matches!(err, ServeError::TooManyOpenStreams));
+        assert_eq!(err.status_code(), 503);
+
+        // a failed resolution doesn't keep its slot
+        drop(first);
+        assert!(server.read_file_stream("/missing.mp4").is_err());
+        assert_eq!(server.open_streams(), 1);
+
+        // the slot of an opened file is held until its last metadata copy is gone
+        drop(meta);
+        assert_eq!(server.open_streams(), 1);
+        drop(copy);
+        assert_eq!(server.open_streams(), 0);
+    }
+
+    #[test]
+    fn extension_policy_filters_served_files() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("site.CSS"), "body {}").unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.map"), "{}").unwrap();
+        fs::write(dir.path().join("LICENSE"), "MIT").unwrap();
+        let set = |extensions: &[&str]| extensions.iter().map(|e| e.to_string()).collect();
+
+        let allow = StaticServer::builder(dir.path())
+            .allowed_extensions(set(&["css", ".JS"]))
+            .denied_extensions(set(&["css"]))
+            .build()
+            .unwrap();
+        // the allowlist takes precedence over the denylist, in any case
+        assert_eq!(allow.read_file("/site.CSS").unwrap().body, b"body {}");
+        assert!(allow.resolve("/app.js").is_some());
+        assert!(allow.resolve("/app.js.map").is_none());
+        assert!(matches!(
+            allow.read_file("/LICENSE"),
+            Err(ServeError::NotFound)
+        ));
+
+        let deny = StaticServer::builder(dir.path())
+            .denied_extensions(set(&["MAP"]))
+            .build()
+            .unwrap();
+        assert!(deny.resolve("/site.CSS").is_some());
+        assert!(matches!(
+            deny.read_file("/app.js.map"),
+            Err(ServeError::NotFound)
+        ));
+        assert_eq!(deny.read_file("/LICENSE").unwrap().body, b"MIT");
+
+        // an empty extension in the allowlist admits files without one
+        let bare = StaticServer::builder(dir.path())
+            .allowed_extensions(set(&[""]))
+            .build()
+            .unwrap();
+        assert!(bare.resolve("/LICENSE").is_some());
+        assert!(bare.resolve("/app.js").is_none());
+    }
+
+    #[test]
+    fn mime_overrides_match_extensions_in_any_case() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("data.JSON"), "{}").unwrap();
+        fs::write(dir.path().join("PHOTO.PNG"), "png").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mime_overrides(HashMap::from([(
+                "Json".to_string(),
+                "application/vnd.custom+json".to_string(),
+            )]))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            server.read_file("/data.JSON").unwrap().mime_type,
+            "application/vnd.custom+json"
+        );
+        assert_eq!(
+            server.read_file("/PHOTO.PNG").unwrap().mime_type,
+            "image/png"
+        );
+    }
+
+    #[test]
+    fn default_charset_is_added_to_text_types_only() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<p>hi</p>").unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("data.json"), "{}").unwrap();
+        fs::write(dir.path().join("logo.png"), "png").unwrap();
+        let mut bom = vec![0xFF, 0xFE];
+        bom.extend_from_slice(b"h\0i\0");
+        fs::write(dir.path().join("wide.txt"), bom).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .default_charset("utf-8")
+            .detect_charset(true)
+            .build()
+            .unwrap();
+        let mime = |path| server.read_file(path).unwrap().mime_type;
+        assert_eq!(mime("/index.html"), "text/html; charset=utf-8");
+        assert_eq!(mime("/app.js"), "text/javascript; charset=utf-8");
+        assert_eq!(mime("/data.json"), "application/json; charset=utf-8");
+        assert_eq!(mime("/logo.png"), "image/png");
+        // a byte order mark wins over the default
+        assert_eq!(mime("/wide.txt"), "text/plain; charset=utf-16");
+        assert_eq!(
+            server.mime_for_request("/about.html"),
+            "text/html; charset=utf-8"
+        );
+
+        let plain = StaticServer::builder(dir.path()).build().unwrap();
+        assert_eq!(
+            plain.read_file("/index.html").unwrap().mime_type,
+            "text/html"
+        );
+
+        assert_eq!(
+            StaticServer::builder(dir.path())
+                .default_charset("utf-8; x=y")
+                .build()
+                .err(),
+            Some(ConfigError::InvalidCharset("utf-8; x=y".to_string()))
+        );
+    }
+
+    #[test]
+    fn mime_overrides_type_wasm_and_unknown_extensions_fall_back() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.wasm"), b"\0asm").unwrap();
+        fs::write(dir.path().join("blob.unknownext"), "?").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mime_overrides(HashMap::from([(
+                ".wasm".to_string(),
+                "application/wasm".to_string(),
+            )]))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            server.read_file("/app.wasm").unwrap().mime_type,
+            "application/wasm"
+        );
+        assert_eq!(server.mime_for_request("/app.wasm"), "application/wasm");
+        assert_eq!(
+            server.read_file("/blob.unknownext").unwrap().mime_type,
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn serves_fresh_precompressed_siblings() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.gz"), "gzip bytes").unwrap();
+        fs::write(dir.path().join("app.js.br"), "brotli bytes").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+
+        let gzip = server
+            .read_file_negotiated("/app.js", Some("gzip, deflate"))
+            .unwrap();
+        assert_eq!(gzip.body, b"gzip bytes");
+        assert_eq!(gzip.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(gzip.mime_type, "text/javascript");
+        assert!(
+            gzip.headers
+                .contains(&("Vary", "Accept-Encoding".to_string()))
+        );
+
+        let brotli = server
+            .read_file_negotiated("/app.js", Some("gzip, deflate, br"))
+            .unwrap();
+        assert_eq!(brotli.body, b"brotli bytes");
+        assert_eq!(brotli.content_encoding.as_deref(), Some("br"));
+
+        let neither = server
+            .read_file_negotiated("/app.js", Some("deflate"))
+            .unwrap();
+        assert_eq!(neither.body, b"let a = 1;");
+        assert_eq!(neither.content_encoding, None);
+
+        // a sibling older than the file is left alone
+        let stale = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(dir.path().join("app.js.br"))
+            .unwrap()
+            .set_modified(stale)
+            .unwrap();
+        let fallback = server
+            .read_file_negotiated("/app.js", Some("br, gzip;q=0.5"))
+            .unwrap();
+        assert_eq!(fallback.content_encoding.as_deref(), Some("gzip"));
+    }
+
+    #[test]
+    fn serves_siblings_with_custom_extensions() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.gzip"), "gzip bytes").unwrap();
+        fs::write(dir.path().join("app.js.gz"), "ignored").unwrap();
+        fs::write(dir.path().join("lone.js.gzip"), gzip_encode(b"let b = 2;")).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_lone_gzip(true)
+            .precompressed_extension(Encoding::Gzip, "gzip")
+            .build()
+            .unwrap();
+        let gzip = server
+            .read_file_negotiated("/app.js", Some("gzip"))
+            .unwrap();
+        assert_eq!(gzip.body, b"gzip bytes");
+        assert_eq!(gzip.content_encoding.as_deref(), Some("gzip"));
+        // a lone sibling stands in for its file under the custom extension too
+        assert_eq!(server.read_file("/lone.js").unwrap().body, b"let b = 2;");
+
+        let dotted = StaticServer::builder(dir.path())
+            .precompressed_extension(Encoding::Gzip, ".gzip")
+            .build()
+            .unwrap();
+        let gzip = dotted
+            .read_file_negotiated("/app.js", Some("gzip"))
+            .unwrap();
+        assert_eq!(gzip.body, b"gzip bytes");
+
+        for extension in ["", "..", "gz/x"] {
+            assert_eq!(
+                StaticServer::builder(dir.path())
+                    .precompressed_extension(Encoding::Gzip, extension)
+                    .build()
+                    .err(),
+                Some(ConfigError::InvalidPrecompressedExtension(
+                    extension.to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn save_data_prefers_the_smallest_encoding() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;".repeat(100)).unwrap();
+        fs::write(dir.path().join("app.js.gz"), "gzip bytes, larger").unwrap();
+        fs::write(dir.path().join("app.js.br"), "brotli bytes").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let mut hints = ClientHints {
+            accept_encoding: Some("gzip, br;q=0.5".to_string()),
+            save_data: false,
+        };
+        let plain = server.read_file_with_hints("/app.js", &hints).unwrap();
+        assert_eq!(plain.content_encoding.as_deref(), Some("gzip"));
+
+        hints.save_data = true;
+        let saving = server.read_file_with_hints("/app.js", &hints).unwrap();
+        assert_eq!(saving.content_encoding.as_deref(), Some("br"));
+        assert_eq!(saving.body, b"brotli bytes");
+
+        // hints read from request headers behave the same
+        let context = RequestContext::default()
+            .with_header("Accept-Encoding", "gzip, br;q=0.5")
+            .with_header("Save-Data", "on");
+        let hints = ClientHints::from_context(&context);
+        let from_headers = server.read_file_with_hints("/app.js", &hints).unwrap();
+        assert_eq!(from_headers.content_encoding.as_deref(), Some("br"));
+    }
+
+    #[test]
+    fn encoded_etags_share_the_identity_base() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.gz"), "gzip bytes").unwrap();
+        fs::write(dir.path().join("app.js.br"), "brotli bytes").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let read = |accept| {
+            server
+                .read_file_negotiated("/app.js", Some(accept))
+                .unwrap()
+        };
+        let (identity, gzip, brotli) = (read("identity"), read("gzip"), read("br"));
+
+        let base = identity.etag.clone().unwrap();
+        let base = base.trim_end_matches('"');
+        assert_eq!(gzip.etag, Some(format!("{}-gzip\"", base)));
+        assert_eq!(brotli.etag, Some(format!("{}-br\"", base)));
+        for file in [&identity, &gzip, &brotli] {
+            assert!(
+                file.headers
+                    .contains(&("Vary", "Accept-Encoding".to_string()))
+            );
+        }
+
+        // a cached encoded copy still revalidates
+        let gzip_etag = gzip.etag.unwrap();
+        assert!(server.is_fresh("/app.js", Some(&gzip_etag), None).unwrap());
+    }
+
+    #[test]
+    fn available_encodings_reports_precompressed_siblings() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.gz"), gzip_encode(b"let a = 1;")).unwrap();
+        fs::write(dir.path().join("plain.js"), "let b = 2;").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        assert_eq!(
+            server.available_encodings("/app.js"),
+            vec![Encoding::Identity, Encoding::Gzip]
+        );
+        assert_eq!(
+            server.available_encodings("/plain.js"),
+            vec![Encoding::Identity]
+        );
+        assert!(server.available_encodings("/missing.js").is_empty());
+    }
+
+    #[test]
+    fn stale_directives_compose_with_cache_control() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "ok").unwrap();
+        let cache_control = |server: &StaticServer| {
+            server
+                .read_file("/app.js")
+                .unwrap()
+                .headers
+                .into_iter()
+                .find(|(name, _)| *name == "Cache-Control")
+                .map(|(_, value)| value)
+        };
+
+        let server = StaticServer::builder(dir.path())
+            .cache_control("public, max-age=600")
+            .stale_while_revalidate(Duration::from_secs(30))
+            .stale_if_error(Duration::from_secs(86400))
+            .build()
+            .unwrap();
+        assert_eq!(
+            cache_control(&server).as_deref(),
+            Some("public, max-age=600, stale-while-revalidate=30, stale-if-error=86400")
+        );
+
+        // without a base directive the stale windows are not sent on their own
+        let server = StaticServer::builder(dir.path())
+            .stale_while_revalidate(Duration::from_secs(30))
+            .build()
+            .unwrap();
+        assert_eq!(cache_control(&server), None);
+    }
+
+    #[test]
+    fn pre_serve_hook_can_deny_a_path() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("public.txt"), "public").unwrap();
+        fs::write(dir.path().join("premium.txt"), "premium").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .pre_serve(|path, context| {
+                let gated = path.ends_with("premium.txt");
+                if gated && context.header("x-token") != Some("valid") {
+                    PreServeDecision::Deny(401)
+                } else {
+                    PreServeDecision::Allow
+                }
+            })
+            .build()
+            .unwrap();
+
+        assert!(server.read_file("/public.txt").is_ok());
+        assert!(matches!(
+            server.read_file("/premium.txt"),
+            Err(ServeError::Denied { status: 401 })
+        ));
+        // a Range header is no way around the veto
+        assert!(matches!(
+            server.read_range("/premium.txt", "bytes=0-2"),
+            Err(ServeError::Denied { status: 401 })
+        ));
+
+        let context = RequestContext::default().with_header("X-Token", "valid");
+        let file = server
+            .read_file_with_context("/premium.txt", &context)
+            .unwrap();
+        assert_eq!(file.body, b"premium");
+    }
+
+    #[test]
+    fn pre_serve_hook_gates_archives_listings_and_sri() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("public.txt"), "public").unwrap();
+        fs::write(dir.path().join("premium.txt"), "premium").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .pre_serve(|path, _| match path.ends_with("premium.txt") {
+                true => PreServeDecision::Deny(401),
+                false => PreServeDecision::Allow,
+            })
+            .build()
+            .unwrap();
+
+        let mut archive = Vec::new();
+        server.tar_directory("/", &mut archive).unwrap();
+        assert_eq!(
+            crate::tar::read_tar(&archive),
+            vec![("public.txt".to_string(), b"public".to_vec())]
+        );
+
+        let names: Vec<String> = server
+            .list_directory_sorted("/")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["public.txt"]);
+        let listing = server.list_directory("/").unwrap();
+        assert!(!String::from_utf8(listing.body).unwrap().contains("premium"));
+
+        let hashes = server.sri_hashes(SriAlgo::Sha256);
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), ["/public.txt"]);
+    }
+
+    #[test]
+    fn only_files_within_the_size_range_are_cached() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("tiny.txt"), "x").unwrap();
+        fs::write(dir.path().join("mid.txt"), "x".repeat(500)).unwrap();
+        fs::write(dir.path().join("huge.txt"), "x".repeat(5_000)).unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .cache(CacheConfig {
+                cache_min_size: 100,
+                cache_max_file_size: Some(1_000),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        for path in ["/tiny.txt", "/mid.txt", "/huge.txt"] {
+            server.read_file(path).unwrap();
+            server.read_file(path).unwrap();
+        }
+
+        let stats = server.cache_stats().unwrap();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.bytes, 500);
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn invalidated_entries_are_read_from_disk_again() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.css"), "a {}").unwrap();
+        fs::write(dir.path().join("app.js"), "let a;").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .cache(CacheConfig::default())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            server.read_file("/app.css").unwrap().source,
+            FileSource::Disk
+        );
+        assert_eq!(
+            server.read_file("/app.css").unwrap().source,
+            FileSource::Cache
+        );
+
+        assert!(server.invalidate("/app.css"));
+        assert!(!server.invalidate("/app.css"));
+        assert_eq!(
+            server.read_file("/app.css").unwrap().source,
+            FileSource::Disk
+        );
+
+        // precompressed siblings go along with their file
+        fs::write(dir.path().join("app.css.gz"), "gzip bytes").unwrap();
+        fs::write(dir.path().join("app.css.br"), "brotli bytes").unwrap();
+        server
+            .read_file_negotiated("/app.css", Some("gzip"))
+            .unwrap();
+        server.read_file_negotiated("/app.css", Some("br")).unwrap();
+        assert_eq!(server.cache_stats().unwrap().entries, 3);
+        assert!(server.invalidate("/app.css"));
+        assert_eq!(server.cache_stats().unwrap().entries, 0);
+
+        server.read_file("/app.js").unwrap();
+        server.invalidate_all();
+        assert_eq!(server.cache_stats().unwrap().entries, 0);
+        assert_eq!(
+            server.read_file("/app.js").unwrap().source,
+            FileSource::Disk
+        );
+    }
+
+    #[test]
+    fn alt_svc_header_is_attached_when_valid() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "ok").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .alt_svc(r#"h3=":443"; ma=86400"#)
+            .build()
+            .unwrap();
+        let file = server.read_file("/app.js").unwrap();
+        assert!(
+            file.headers
+                .contains(&("Alt-Svc", r#"h3=":443"; ma=86400"#.to_string()))
+        );
+
+        // the builder refuses the value, and from_config drops it
+        let built = StaticServer::builder(dir.path())
+            .alt_svc("h3=\":443\"\r\nSet-Cookie: x=1")
+            .build();
+        assert_eq!(
+            built.err(),
+            Some(ConfigError::InvalidHeaderValue { header: "Alt-Svc" })
+        );
+        let server = StaticServer::from_config(StaticServerConfig {
+            root_dir: dir.path().to_path_buf(),
+            alt_svc: Some("h3=\":443\"\r\nSet-Cookie: x=1".to_string()),
+            ..Default::default()
+        });
+        let file = server.read_file("/app.js").unwrap();
+        assert!(file.headers.iter().all(|(name, _)| *name != "Alt-Svc"));
+    }
+
+    #[test]
+    fn serves_error_pages_per_status() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("403.html"), "no entry").unwrap();
+        fs::write(dir.path().join("404.html"), "lost").unwrap();
+        fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_hidden(false)
+            .error_pages(HashMap::from([
+                (403, PathBuf::from("403.html")),
+                (404, PathBuf::from("404.html")),
+            ]))
+            .build()
+            .unwrap();
+
+        let forbidden = server.read_file("/.env").unwrap();
+        assert_eq!(
+            (forbidden.status, forbidden.body.as_slice()),
+            (403, &b"no entry"[..])
+        );
+
+        let missing = server.read_file("/missing.txt").unwrap();
+        assert_eq!(
+            (missing.status, missing.body.as_slice()),
+            (404, &b"lost"[..])
+        );
+
+        assert_eq!(server.error_response(404).unwrap().status, 404);
+        assert!(server.error_response(500).is_none());
+    }
+
+    #[test]
+    fn skips_html_error_pages_for_json_clients() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("404.html"), "lost").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .error_pages(HashMap::from([(404, PathBuf::from("404.html"))]))
+            .build()
+            .unwrap();
+
+        let api = RequestContext::default().with_header("Accept", "application/json");
+        assert!(matches!(
+            server.read_file_with_context("/missing.txt", &api),
+            Err(ServeError::NotFound)
+        ));
+
+        let browser =
+            RequestContext::default().with_header("Accept", "text/html,application/json;q=0.9");
+        let page = server
+            .read_file_with_context("/missing.txt", &browser)
+            .unwrap();
+        assert_eq!((page.status, page.body.as_slice()), (404, &b"lost"[..]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tenant_jail_keeps_symlinks_inside_the_tenant() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("tenantA/assets")).unwrap();
+        fs::create_dir_all(dir.path().join("tenantB")).unwrap();
+        fs::write(dir.path().join("tenantA/assets/logo.svg"), "<svg/>").unwrap();
+        fs::write(dir.path().join("tenantB/secret.txt"), "B only").unwrap();
+        std::os::unix::fs::symlink(
+            dir.path().join("tenantA/assets"),
+            dir.path().join("tenantA/static"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            dir.path().join("tenantB/secret.txt"),
+            dir.path().join("tenantA/borrowed.txt"),
+        )
+        .unwrap();
+
+        let jailed = StaticServer::builder(dir.path())
+            .per_tenant_jail(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            jailed.read_file("/tenantA/static/logo.svg").unwrap().body,
+            b"<svg/>"
+        );
+        assert!(matches!(
+            jailed.read_file("/tenantA/borrowed.txt"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            jailed.read_file("/./tenantA/borrowed.txt"),
+            Err(ServeError::NotFound)
+        ));
+        assert_eq!(
+            jailed.read_file("/tenantB/secret.txt").unwrap().body,
+            b"B only"
+        );
+
+        // without the jail the link stays within root and is served
+        let shared = StaticServer::builder(dir.path()).build().unwrap();
+        assert_eq!(
+            shared.read_file("/tenantA/borrowed.txt").unwrap().body,
+            b"B only"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_through_a_symlink_to_a_directory() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("public");
+        fs::create_dir_all(root.join("real")).unwrap();
+        fs::write(root.join("real/index.html"), "real index").unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link-to-dir")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("link-to-outside")).unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let server = StaticServer::builder(&root)
+            .mount_path("/static")
+            .serve_index(true)
+            .content_location(true)
+            .build()
+            .unwrap();
+
+        let direct = server.read_file("/static/link-to-dir/index.html").unwrap();
+        assert_eq!(direct.body, b"real index");
+
+        // the index is found through the link, and Content-Location keeps the requested name
+        let index = server.read_file("/static/link-to-dir/").unwrap();
+        assert_eq!(index.body, b"real index");
+        assert!(index.headers.contains(&(
+            "Content-Location",
+            "/static/link-to-dir/index.html".to_string()
+        )));
+
+        assert!(matches!(
+            server.read_file("/static/link-to-outside/secret.txt"),
+            Err(ServeError::NotFound)
+        ));
+
+        let strict = StaticServer::builder(&root)
+            .mount_path("/static")
+            .follow_symlinks(false)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            strict.read_file("/static/link-to-dir/index.html"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn refuses_trace_and_connect() {
+        let dir = tempdir().unwrap();
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+
+        for method in ["TRACE", "CONNECT", "trace"] {
+            let err = server.check_method(method).unwrap_err();
+            assert!(matches!(err, ServeError::MethodNotAllowed { .. }));
+            assert_eq!(err.status_code(), 405);
+        }
+        assert!(server.check_method("GET").is_ok());
+        assert!(server.check_method("HEAD").is_ok());
+        assert!(server.check_method("POST").is_err());
+    }
+
+    #[test]
+    fn spa_fallback_serves_the_root_index_for_routes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<div id=app>").unwrap();
+        fs::write(dir.path().join("logo.png"), "png").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .spa_fallback(true)
+            .build()
+            .unwrap();
+
+        let route = server.read_file("/static/users/42").unwrap();
+        assert_eq!(route.body, b"<div id=app>");
+        assert_eq!(route.mime_type, "text/html");
+        assert_eq!(server.read_file("/static/logo.png").unwrap().body, b"png");
+        assert!(matches!(
+            server.read_file("/static/missing.png"),
+            Err(ServeError::NotFound)
+        ));
+
+        let plain = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .build()
+            .unwrap();
+        assert!(matches!(
+            plain.read_file("/static/users/42"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn injects_meta_tags_into_html_heads() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("posts")).unwrap();
+        fs::write(
+            dir.path().join("posts/hello.html"),
+            "<html><head><title>Hello</title></head></html>",
+        )
+        .unwrap();
+        fs::write(dir.path().join("posts/hello.txt"), "<head></head>").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .html_meta_injector(|path| {
+                let stem = path.file_stem().unwrap().to_string_lossy();
+                vec![("og:title".to_string(), stem.into_owned())]
+            })
+            .build()
+            .unwrap();
+
+        let page = server.read_file("/posts/hello.html").unwrap();
+        assert_eq!(
+            page.body,
+            br#"<html><head><meta property="og:title" content="hello"><title>Hello</title></head></html>"#
+        );
+        assert!(page.etag.unwrap().starts_with("W/"));
+
+        // other types are left alone
+        let text = server.read_file("/posts/hello.txt").unwrap();
+        assert_eq!(text.body, b"<head></head>");
+    }
+
+    #[test]
+    fn transformed_bodies_get_content_hash_etags() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let  a  =  1;").unwrap();
+
+        let squash = StaticServer::builder(dir.path())
+            .transform(|_, _, body| body.into_iter().filter(|b| *b != b' ').collect())
+            .build()
+            .unwrap();
+        let upper = StaticServer::builder(dir.path())
+            .transform(|_, _, body| body.to_ascii_uppercase())
+            .build()
+            .unwrap();
+
+        let squashed = squash.read_file("/app.js").unwrap();
+        assert_eq!(squashed.body, b"leta=1;");
+        let squashed_etag = squashed.etag.unwrap();
+        assert!(squashed_etag.starts_with("W/\""));
+
+        let upper_etag = upper.read_file("/app.js").unwrap().etag.unwrap();
+        assert_ne!(squashed_etag, upper_etag);
+
+        assert!(
+            squash
+                .is_fresh("/app.js", Some(&squashed_etag), None)
+                .unwrap()
+        );
+        assert!(
+            !upper
+                .is_fresh("/app.js", Some(&squashed_etag), None)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn service_worker_gets_scope_and_no_cache() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("js")).unwrap();
+        fs::write(dir.path().join("js/sw.js"), "self.skipWaiting();").unwrap();
+        fs::write(dir.path().join("js/app.js"), "app();").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .cache_control("public, max-age=3600")
+            .service_worker("/js/sw.js", "/")
+            .build()
+            .unwrap();
+
+        let worker = server.read_file("/static/js/sw.js").unwrap();
+        assert!(
+            worker
+                .headers
+                .contains(&("Service-Worker-Allowed", "/".to_string()))
+        );
+        assert!(
+            worker
+                .headers
+                .contains(&("Cache-Control", "no-cache".to_string()))
+        );
+        assert!(
+            !worker
+                .headers
+                .contains(&("Cache-Control", "public, max-age=3600".to_string()))
+        );
+
+        let app = server.read_file("/static/js/app.js").unwrap();
+        assert!(
+            app.headers
+                .iter()
+                .all(|(name, _)| *name != "Service-Worker-Allowed")
+        );
+
+        // a streamed worker carries the same headers as a buffered one
+        let streamed = server.read_file_stream("/static/js/sw.js").unwrap();
+        assert_eq!(streamed.headers, worker.headers);
+    }
+
+    #[test]
+    fn head_requests_never_read_the_body() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("movie.mp4"), vec![0u8; 4096]).unwrap();
+        let vfs = Arc::new(crate::vfs::mock::CountingFs::default());
+
+        let server = StaticServer::builder(dir.path())
+            .nosniff(true)
+            .vfs(vfs.clone())
+            .build()
+            .unwrap();
+
+        let head = server.read_file_head("/movie.mp4").unwrap();
+        assert!(head.body.is_empty());
+        assert_eq!(head.len, 4096);
+        assert_eq!(head.mime_type, "video/mp4");
+        assert_eq!(head.status, 200);
+
+        let get = server.read_file("/movie.mp4").unwrap();
+        assert_eq!(head.headers, get.headers);
+        assert_eq!(head.etag, get.etag);
+        assert_eq!(get.len, 4096);
+
+        // only the GET read the file
+        assert_eq!(vfs.count_for("read", "movie.mp4"), 1);
+        assert_eq!(vfs.count_for("open", "movie.mp4"), 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_read_times_out_on_a_slow_filesystem() {
+        use crate::vfs::{AsyncVfs, VfsFuture};
+
+        struct SlowFs;
+        impl AsyncVfs for SlowFs {
+            fn read<'a>(&'a self, _path: &'a Path, _nofollow: bool) -> VfsFuture<'a, Vec<u8>> {
+                Box::pin(async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(Vec::new())
+                })
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("report.pdf"), "pdf").unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        let fast = StaticServer::builder(dir.path())
+            .read_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let file = runtime
+            .block_on(fast.read_file_async("/report.pdf"))
+            .unwrap();
+        assert_eq!(file.body, b"pdf");
+
+        let slow = StaticServer::builder(dir.path())
+            .read_timeout(Duration::from_millis(20))
+            .async_vfs(Arc::new(SlowFs))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            runtime.block_on(slow.read_file_async("/report.pdf")),
+            Err(ServeError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn sniff_table_types_unknown_files_by_magic_bytes() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("level.dat"), b"LVL1\x00\x02map").unwrap();
+        fs::write(dir.path().join("other.dat"), b"junk").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"LVL1 notes").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .sniff_table(vec![(
+                b"LVL1".to_vec(),
+                "application/x-game-level".to_string(),
+            )])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            server.read_file("/level.dat").unwrap().mime_type,
+            "application/x-game-level"
+        );
+        assert_eq!(
+            server.read_file("/other.dat").unwrap().mime_type,
+            "application/octet-stream"
+        );
+        // a type guessed from the extension is never second-guessed
+        assert_eq!(
+            server.read_file("/notes.txt").unwrap().mime_type,
+            "text/plain"
+        );
+    }
+
+    #[test]
+    fn detects_the_charset_from_a_byte_order_mark() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("legacy.txt"), b"\xFF\xFEh\x00i\x00").unwrap();
+        fs::write(dir.path().join("marked.css"), b"\xEF\xBB\xBFa {}").unwrap();
+        fs::write(dir.path().join("plain.txt"), b"hi").unwrap();
+        fs::write(dir.path().join("image.png"), b"\xFF\xFEnot text").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .detect_charset(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            server.read_file("/legacy.txt").unwrap().mime_type,
+            "text/plain; charset=utf-16"
+        );
+        assert_eq!(
+            server.read_file("/marked.css").unwrap().mime_type,
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(
+            server.read_file("/plain.txt").unwrap().mime_type,
+            "text/plain"
+        );
+        assert_eq!(
+            server.read_file("/image.png").unwrap().mime_type,
+            "image/png"
+        );
+
+        let off = StaticServer::builder(dir.path()).build().unwrap();
+        assert_eq!(
+            off.read_file("/legacy.txt").unwrap().mime_type,
+            "text/plain"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tars_a_directory_with_the_serving_policies() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("public");
+        fs::create_dir_all(root.join("docs/guides")).unwrap();
+        fs::write(root.join("docs/readme.txt"), "read me").unwrap();
+        fs::write(root.join("docs/guides/setup.md"), "# Setup").unwrap();
+        fs::write(root.join("docs/.env"), "SECRET=1").unwrap();
+        fs::write(root.join("outside.txt"), "not in docs").unwrap();
+        fs::write(dir.path().join("private.txt"), "private").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("private.txt"), root.join("docs/leak.txt"))
+            .unwrap();
+
+        let server = StaticServer::builder(&root)
+            .mount_path("/static")
+            .serve_hidden(false)
+            .build()
+            .unwrap();
+
+        let mut archive = Vec::new();
+        server.tar_directory("/static/docs/", &mut archive).unwrap();
+        let mut files = crate::tar::read_tar(&archive);
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                ("guides/setup.md".to_string(), b"# Setup".to_vec()),
+                ("readme.txt".to_string(), b"read me".to_vec()),
+            ]
+        );
+
+        let mut compressed = Vec::new();
+        server
+            .tar_gz_directory("/static/docs", &mut compressed)
+            .unwrap();
+        assert_eq!(
+            crate::gzip::gzip_decode(&compressed, None).unwrap(),
+            archive
+        );
+
+        let mut ignored = Vec::new();
+        assert!(matches!(
+            server.tar_directory("/static/docs/readme.txt", &mut ignored),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            server.tar_directory("/static/../", &mut ignored),
+            Err(ServeError::NotFound)
+        ));
+        assert!(ignored.is_empty());
+    }
+
+    #[test]
+    fn tars_within_the_file_size_limit() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), vec![b'a'; 600]).unwrap();
+        fs::write(dir.path().join("b.txt"), vec![b'b'; 600]).unwrap();
+        fs::write(dir.path().join("huge.bin"), vec![0u8; 4096]).unwrap();
+
+        // files over the limit are left out
+        let roomy = StaticServer::builder(dir.path())
+            .max_file_size(2048)
+            .build()
+            .unwrap();
+        let mut archive = Vec::new();
+        roomy.tar_directory("/", &mut archive).unwrap();
+        let mut names: Vec<String> = crate::tar::read_tar(&archive)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+
+        // and the files together may not pass it either
+        let strict = StaticServer::builder(dir.path())
+            .max_file_size(1024)
+            .build()
+            .unwrap();
+        let mut ignored = Vec::new();
+        assert!(matches!(
+            strict.tar_directory("/", &mut ignored),
+            Err(ServeError::TooLarge {
+                size: 1200,
+                limit: 1024
+            })
+        ));
+        assert!(ignored.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tars_around_unreadable_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // root bypasses file permissions, so the check can't be observed
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("open.txt"), "open").unwrap();
+        let locked = dir.path().join("locked.txt");
+        fs::write(&locked, "locked").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let mut archive = Vec::new();
+        let result = server.tar_directory("/", &mut archive);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o644)).unwrap();
+
+        result.unwrap();
+        assert_eq!(
+            crate::tar::read_tar(&archive),
+            vec![("open.txt".to_string(), b"open".to_vec())]
+        );
+    }
+
+    #[test]
+    fn sri_hashes_cover_every_servable_file() {
+        use base64::Engine;
+
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("js")).unwrap();
+        fs::write(dir.path().join("js/app.js"), "app();").unwrap();
+        fs::write(dir.path().join("site.css"), "a {}").unwrap();
+        fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .mount_path("/static")
+            .serve_hidden(false)
+            .build()
+            .unwrap();
+        let hashes = server.sri_hashes(SriAlgo::Sha384);
+
+        let mut paths: Vec<&str> = hashes.keys().map(String::as_str).collect();
+        paths.sort();
+        assert_eq!(paths, ["/static/js/app.js", "/static/site.css"]);
+
+        let integrity = &hashes["/static/js/app.js"];
+        let digest = integrity.strip_prefix("sha384-").unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(digest)
+            .unwrap();
+        assert_eq!(decoded.len(), 48);
+    }
+
+    #[test]
+    fn bare_directory_policy_decides_the_answer() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("assets")).unwrap();
+        fs::write(dir.path().join("assets/app.js"), "app();").unwrap();
+
+        let server_with = |policy| {
+            StaticServer::builder(dir.path())
+                .mount_path("/static")
+                .bare_directory(policy)
+                .build()
+                .unwrap()
+        };
+
+        assert!(matches!(
+            server_with(BareDirectoryPolicy::NotFound).read_file("/static/assets/"),
+            Err(ServeError::NotFound)
+        ));
+        assert!(matches!(
+            server_with(BareDirectoryPolicy::Forbidden).read_file("/static/assets"),
+            Err(ServeError::Forbidden)
+        ));
+
+        let redirect = server_with(BareDirectoryPolicy::Redirect("/".to_string()));
+        match redirect.read_file("/static/assets/") {
+            Err(err @ ServeError::Redirect { .. }) => {
+                assert_eq!(err.status_code(), 302);
+                assert!(matches!(err, ServeError::Redirect { location, .. } if location == "/"));
+            }
+            other => panic!("expected a redirect, got {:?}", other),
+        }
+        // missing paths are still plain misses
+        assert!(matches!(
+            redirect.read_file("/static/nope/"),
+            Err(ServeError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn mount_root_policy_decides_the_bare_mount_path() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "home").unwrap();
+
+        let server_with = |policy| {
+            StaticServer::builder(dir.path())
+                .mount_path("/static")
+                .serve_index(true)
+                .mount_root(policy)
+                .build()
+                .unwrap()
+        };
+
+        let serve = server_with(MountRootPolicy::ServeIndex);
+        assert_eq!(serve.read_file("/static").unwrap().body, b"home");
+        assert_eq!(serve.read_file("/static/").unwrap().body, b"home");
+
+        let redirect = server_with(MountRootPolicy::Redirect);
+        match redirect.read_file("/static") {
+            Err(ServeError::Redirect { status, location }) => {
+                assert_eq!((status, location.as_str()), (301, "/static/"));
+            }
+            other => panic!("expected a redirect, got {:?}", other),
+        }
+        assert_eq!(redirect.read_file("/static/").unwrap().body, b"home");
+
+        let not_found = server_with(MountRootPolicy::NotFound);
+        assert!(matches!(
+            not_found.read_file("/static"),
+            Err(ServeError::NotFound)
+        ));
+        assert_eq!(not_found.read_file("/static/").unwrap().body, b"home");
+    }
+
+    #[test]
+    fn content_digest_header_covers_the_body() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("hello.txt"), "hello").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .content_digest(DigestAlgo::Sha256)
+            .build()
+            .unwrap();
+
+        let file = server.read_file("/hello.txt").unwrap();
+        assert!(file.headers.contains(&(
+            "Content-Digest",
+            "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:".to_string()
+        )));
+        // HEAD carries the same headers, without the body
+        let head = server.read_file_head("/hello.txt").unwrap();
+        assert_eq!((head.headers, head.len), (file.headers, 5));
+    }
+
+    #[test]
+    fn head_requests_vary_like_get() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let a = 1;").unwrap();
+        fs::write(dir.path().join("app.js.gz"), "gzip bytes").unwrap();
+        fs::write(dir.path().join("plain.js"), "let b = 2;").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        for path in ["/app.js", "/plain.js"] {
+            let get = server.read_file(path).unwrap();
+            let head = server.read_file_head(path).unwrap();
+            assert_eq!(head.headers, get.headers, "{path}");
+        }
+        assert!(
+            server
+                .read_file_head("/app.js")
+                .unwrap()
+                .headers
+                .contains(&("Vary", "Accept-Encoding".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_dots_and_spaces() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+
+        for path in [
+            "/secret.txt.",
+            "/secret.txt%20",
+            "/dir./secret.txt",
+            "/secret.txt...",
+        ] {
+            assert!(matches!(
+                server.read_file(path),
+                Err(ServeError::InvalidPath)
+            ));
+        }
+        assert!(server.read_file("/secret.txt").is_ok());
+        assert!(server.read_file("/./secret.txt").is_ok());
+    }
+
+    #[test]
+    fn timing_allow_origin_header_is_attached_when_valid() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("font.woff2"), "font").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .timing_allow_origin("https://app.example.com")
+            .build()
+            .unwrap();
+        let file = server.read_file("/font.woff2").unwrap();
+        assert!(
+            file.headers
+                .contains(&("Timing-Allow-Origin", "https://app.example.com".to_string()))
+        );
+
+        let built = StaticServer::builder(dir.path())
+            .timing_allow_origin("*\nX-Injected: 1")
+            .build();
+        assert!(matches!(
+            built,
+            Err(ConfigError::InvalidHeaderValue {
+                header: "Timing-Allow-Origin"
+            })
+        ));
+        let server = StaticServer::from_config(StaticServerConfig {
+            root_dir: dir.path().to_path_buf(),
+            timing_allow_origin: Some("*\nX-Injected: 1".to_string()),
+            ..Default::default()
+        });
+        let file = server.read_file("/font.woff2").unwrap();
+        assert!(
+            file.headers
+                .iter()
+                .all(|(name, _)| *name != "Timing-Allow-Origin")
+        );
+    }
+
+    #[test]
+    fn cross_origin_isolation_headers_are_attached() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("worker.js"), "postMessage(1);").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .cross_origin_resource_policy("same-origin")
+            .cross_origin_embedder_policy("require-corp")
+            .build()
+            .unwrap();
+        let file = server.read_file("/worker.js").unwrap();
+        assert_eq!(
+            file.header("Cross-Origin-Resource-Policy"),
+            Some("same-origin")
+        );
+        assert_eq!(
+            file.header("Cross-Origin-Embedder-Policy"),
+            Some("require-corp")
+        );
+        let head = server.read_file_head("/worker.js").unwrap();
+        assert_eq!(
+            head.header("Cross-Origin-Embedder-Policy"),
+            Some("require-corp")
+        );
+
+        assert_eq!(
+            StaticServer::builder(dir.path())
+                .cross_origin_resource_policy("anyone")
+                .build()
+                .err(),
+            Some(ConfigError::InvalidHeaderValue {
+                header: "Cross-Origin-Resource-Policy"
+            })
+        );
+        assert_eq!(
+            StaticServer::builder(dir.path())
+                .cross_origin_embedder_policy("Require-Corp")
+                .build()
+                .err(),
+            Some(ConfigError::InvalidHeaderValue {
+                header: "Cross-Origin-Embedder-Policy"
+            })
+        );
+
+        let plain = StaticServer::builder(dir.path()).build().unwrap();
+        let file = plain.read_file("/worker.js").unwrap();
+        assert_eq!(file.header("Cross-Origin-Resource-Policy"), None);
+    }
+
+    #[test]
+    fn combined_index_etag_tracks_sibling_assets() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("index.html"),
+            r#"<script src="app.3f2a.js"></script>"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("app.3f2a.js"), "v1").unwrap();
+
+        let server = StaticServer::builder(dir.path())
+            .serve_index(true)
+            .combined_index_etag(true)
+            .build()
+            .unwrap();
+
+        let before = server.read_file("/").unwrap().etag.unwrap();
+        assert_eq!(
+            server.read_file("/index.html").unwrap().etag.unwrap(),
+            before
+        );
+        assert_eq!(server.read_file_head("/").unwrap().etag.unwrap(), before);
+
+        // same length, so only the content hash can tell the versions apart
+        fs::write(dir.path().join("app.3f2a.js"), "v2").unwrap();
+        let after = server.read_file("/").unwrap().etag.unwrap();
+        assert_ne!(before, after);
+        assert!(server.is_fresh("/", Some(&after), None).unwrap());
+
+        // assets keep their usual tags
+        let asset = server.read_file("/app.3f2a.js").unwrap().etag.unwrap();
+        assert!(asset.starts_with("\"2-"), "{asset}");
+    }
+
+    #[test]
+    fn if_none_match_alone_decides_conditional_reads() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("style.css"), "body{}").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let etag = server.read_file("/style.css").unwrap().etag.unwrap();
+        // a strong tag made of the length and mtime
+        assert!(etag.starts_with("\"6-") && etag.ends_with('"'), "{etag}");
+
+        let not_modified = server
+            .read_file_conditional("/style.css", Some(&etag), None)
+            .unwrap();
+        assert_eq!(not_modified.status, 304);
+        assert!(not_modified.body.is_empty());
+
+        let full = server
+            .read_file_conditional("/style.css", Some("\"6-0\""), None)
+            .unwrap();
+        assert_eq!((full.status, full.body.as_slice()), (200, &b"body{}"[..]));
+        assert_eq!(full.etag.as_deref(), Some(etag.as_str()));
+    }
+
+    #[test]
+    fn conditional_reads_log_the_final_status_and_use_error_pages() {
+        use std::sync::Mutex;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("style.css"), "body{}").unwrap();
+        fs::write(dir.path().join("404.html"), "<h1>Lost?</h1>").unwrap();
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink_records = Arc::clone(&records);
+        let server = StaticServer::builder(dir.path())
+            .not_found_file("404.html")
+            .access_log(move |record: &AccessLogRecord| {
+                sink_records.lock().unwrap().push(record.status)
+            })
+            .build()
+            .unwrap();
+        let etag = server.read_file("/style.css").unwrap().etag.unwrap();
+
+        let not_modified = server
+            .read_file_conditional("/style.css", Some(&etag), None)
+            .unwrap();
+        assert_eq!(not_modified.status, 304);
+        // a miss is answered by the 404 page, not a bare error
+        let missing = server
+            .read_file_conditional("/missing.css", Some(&etag), None)
+            .unwrap();
+        assert_eq!(
+            (missing.status, missing.body.as_slice()),
+            (404, &b"<h1>Lost?</h1>"[..])
+        );
+
+        assert_eq!(*records.lock().unwrap(), [200, 304, 404]);
+    }
+
+    #[test]
+    fn if_modified_since_compares_against_the_file_mtime() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("logo.svg");
+        fs::write(&path, "<svg/>").unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let file = server.read_file("/logo.svg").unwrap();
+        assert_eq!(file.modified, Some(mtime));
+        assert_eq!(format_http_date(mtime), "Tue, 14 Nov 2023 22:13:20 GMT");
+
+        let future = parse_http_date("Wed, 15 Nov 2023 00:00:00 GMT");
+        let not_modified = server
+            .read_file_conditional("/logo.svg", None, future)
+            .unwrap();
+        assert_eq!(not_modified.status, 304);
+        assert_eq!(not_modified.modified, Some(mtime));
+
+        let past = parse_http_date("Tuesday, 14-Nov-23 22:13:19 GMT");
+        let modified = server
+            .read_file_conditional("/logo.svg", None, past)
+            .unwrap();
+        assert_eq!(
+            (modified.status, modified.body.as_slice()),
+            (200, &b"<svg/>"[..])
+        );
+    }
+
+    #[test]
+    fn etag_wins_over_a_conflicting_date() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "console.log(1)").unwrap();
+
+        let server = StaticServer::builder(dir.path()).build().unwrap();
+        let etag = server.read_file("/app.js").unwrap().etag.unwrap();
+        let modified = fs::metadata(dir.path().join("app.js"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        let long_ago = modified - Duration::from_secs(3600);
+        let much_later = modified + Duration::from_secs(3600);
+
+        // matching ETag, date says modified: 304
+        let response = server
+            .read_file_conditional("/app.js", Some(&etag), Some(long_ago))
+            .unwrap();
+        assert_eq!(response.status, 304);
+        assert!(response.body.is_empty());
+        assert_eq!(response.etag.as_deref(), Some(etag.as_str()));
+
+        // mismatching ETag, date says not modified: 200
+        let response = server
+            .read_file_conditional("/app.js", Some("\"other\""), Some(much_later))
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"console.log(1)");
+    }
+
+    #[test]
+    fn from_bytes_builds_a_complete_response() {
+        let file = StaticFile::from_bytes(b"{\"ok\":true}".to_vec(), "application/json")
+            .with_etag("\"v1\"")
+            .with_cache_control("no-cache")
+            .with_cache_control("max-age=60");
+
+        assert_eq!(file.status, 200);
+        assert_eq!(file.len, 11);
+        assert_eq!(file.mime_type, "application/json");
+        assert_eq!(file.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            file.headers,
+            vec![("Cache-Control", "max-age=60".to_string())]
+        );
+        assert_eq!(file.source, FileSource::Disk);
+    }
+
+    #[test]
+    fn lists_every_mime_guess_for_an_extension() {
+        let guesses = guess_mime_types(Path::new("font.TTF"));
+        assert!(guesses.len() > 1, "{guesses:?}");
+        assert_eq!(guesses[0], guess_mime_type(Path::new("font.ttf")));
+        assert!(guess_mime_types(Path::new("no-extension")).is_empty());
     }
 }
@@ -0,0 +1,120 @@
+// src/stream.rs
+
+// dependencies
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+// enum type which represents how a streamed body should be framed on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamMode {
+    // the final length is known up front: send Content-Length and stream the body
+    ContentLength,
+    // the body is produced by a transform whose output length isn't known up front: use
+    // chunked transfer encoding (or the HTTP/2 equivalent) and omit Content-Length
+    Chunked,
+}
+
+// struct type which represents a static file whose body is read incrementally
+pub struct StaticFileStream {
+    pub mime_type: Cow<'static, str>,
+    pub path: PathBuf,
+    pub headers: Vec<(&'static str, String)>,
+    pub status: u16,
+    pub etag: Option<String>,
+    // the file's modification time, for the Last-Modified header
+    pub modified: Option<SystemTime>,
+    // the body length in bytes; only known when the mode is ContentLength
+    pub len: Option<u64>,
+    pub mode: StreamMode,
+    reader: Box<dyn Read + Send>,
+    guard: Option<StreamGuard>,
+}
+
+// struct type which represents one slot of the open-stream limit, released on drop
+#[derive(Debug)]
+pub struct StreamGuard {
+    open: Arc<AtomicUsize>,
+}
+
+// methods for the StreamGuard type
+impl StreamGuard {
+    // take a slot from the counter, or None when the limit is already reached
+    pub(crate) fn acquire(open: &Arc<AtomicUsize>, limit: usize) -> Option<Self> {
+        open.fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+            (count < limit).then_some(count + 1)
+        })
+        .ok()?;
+        Some(StreamGuard {
+            open: Arc::clone(open),
+        })
+    }
+}
+
+// implement the Drop trait for the StreamGuard type, giving the slot back
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// methods for the StaticFileStream type
+impl StaticFileStream {
+    // wrap a reader whose exact length is known
+    pub(crate) fn with_len(reader: Box<dyn Read + Send>, len: u64) -> Self {
+        StaticFileStream {
+            mime_type: Cow::Borrowed("application/octet-stream"),
+            path: PathBuf::new(),
+            headers: Vec::new(),
+            status: 200,
+            etag: None,
+            modified: None,
+            len: Some(len),
+            mode: StreamMode::ContentLength,
+            reader,
+            guard: None,
+        }
+    }
+
+    // wrap a reader producing transformed output of unknown length
+    pub(crate) fn chunked(reader: Box<dyn Read + Send>) -> Self {
+        StaticFileStream {
+            len: None,
+            mode: StreamMode::Chunked,
+            ..StaticFileStream::with_len(reader, 0)
+        }
+    }
+
+    // hold an open-stream slot for as long as the stream lives
+    pub(crate) fn with_guard(mut self, guard: Option<StreamGuard>) -> Self {
+        self.guard = guard;
+        self
+    }
+}
+
+// implement the Read trait for the StaticFileStream type, yielding the body
+impl Read for StaticFileStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+// implement the Debug trait for the StaticFileStream type, leaving out the reader
+impl fmt::Debug for StaticFileStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticFileStream")
+            .field("mime_type", &self.mime_type)
+            .field("path", &self.path)
+            .field("headers", &self.headers)
+            .field("status", &self.status)
+            .field("etag", &self.etag)
+            .field("modified", &self.modified)
+            .field("len", &self.len)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
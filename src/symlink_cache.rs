@@ -0,0 +1,113 @@
+// src/symlink_cache.rs
+
+// dependencies
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// struct type which remembers where requested paths canonicalized to, for a limited time.
+// Tradeoffs: within the TTL a retargeted symlink keeps resolving to its old target, so the
+// server may keep serving the old file; containment within the root and the existence of the
+// target are still re-checked on every hit, so a cached entry can never leak a path outside
+// the root or resurrect a deleted file
+pub(crate) struct SymlinkCache {
+    ttl: Duration,
+    capacity: usize,
+    inner: Mutex<SymlinkInner>,
+}
+
+// struct type which represents the mutable state of the symlink cache
+#[derive(Default)]
+struct SymlinkInner {
+    // the canonical path and when it was stored, plus an insertion counter to order
+    // same-instant entries
+    entries: HashMap<PathBuf, (PathBuf, Instant, u64)>,
+    clock: u64,
+}
+
+// methods for the SymlinkCache type
+impl SymlinkCache {
+    pub(crate) fn new(ttl: Duration, capacity: usize) -> Self {
+        SymlinkCache {
+            ttl,
+            capacity,
+            inner: Mutex::new(SymlinkInner::default()),
+        }
+    }
+
+    // the cached canonical path for a requested path, if it hasn't expired
+    pub(crate) fn get(&self, path: &Path) -> Option<PathBuf> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        match inner.entries.get(path) {
+            Some((canonical, stored, _)) if stored.elapsed() < self.ttl => Some(canonical.clone()),
+            Some(_) => {
+                inner.entries.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    // remember the canonical path for a requested path; when full, expired entries go first
+    // and then the oldest one, so the map never grows past its capacity
+    pub(crate) fn insert(&self, path: PathBuf, canonical: PathBuf) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let grows = !inner.entries.contains_key(&path);
+        if grows && inner.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            inner
+                .entries
+                .retain(|_, (_, stored, _)| stored.elapsed() < ttl);
+        }
+        if grows
+            && inner.entries.len() >= self.capacity
+            && let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, _, order))| *order)
+                .map(|(path, _)| path.clone())
+        {
+            inner.entries.remove(&oldest);
+        }
+        inner.clock += 1;
+        let order = inner.clock;
+        inner
+            .entries
+            .insert(path, (canonical, Instant::now(), order));
+    }
+
+    // forget every cached resolution, e.g. after a file watcher saw the tree change
+    pub(crate) fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_entries_and_stays_within_capacity() {
+        let cache = SymlinkCache::new(Duration::from_secs(60), 2);
+        for name in ["a", "b", "c"] {
+            cache.insert(PathBuf::from(name), PathBuf::from(format!("/root/{name}")));
+        }
+        assert_eq!(cache.inner.lock().unwrap().entries.len(), 2);
+        assert_eq!(cache.get(Path::new("a")), None);
+        assert_eq!(cache.get(Path::new("c")), Some(PathBuf::from("/root/c")));
+
+        // refreshing an existing entry never evicts another
+        cache.insert(PathBuf::from("c"), PathBuf::from("/root/c2"));
+        assert_eq!(cache.get(Path::new("b")), Some(PathBuf::from("/root/b")));
+
+        let expired = SymlinkCache::new(Duration::ZERO, 8);
+        expired.insert(PathBuf::from("a"), PathBuf::from("/root/a"));
+        assert_eq!(expired.get(Path::new("a")), None);
+    }
+}
@@ -0,0 +1,131 @@
+// src/tar.rs
+
+// dependencies
+use std::io::{self, Write};
+
+// size of a tar header and of the blocks file data is padded to
+const BLOCK: usize = 512;
+
+// struct type which represents a writer of ustar archives holding regular files
+pub(crate) struct TarWriter<W: Write> {
+    writer: W,
+}
+
+// methods for the TarWriter type
+impl<W: Write> TarWriter<W> {
+    pub fn new(writer: W) -> Self {
+        TarWriter { writer }
+    }
+
+    // add a regular file; names longer than 100 bytes are split into the ustar prefix field
+    // at a `/`, and a name which can't be split that way is rejected
+    pub fn append(&mut self, name: &str, data: &[u8], mtime: u64) -> io::Result<()> {
+        let mut header = [0u8; BLOCK];
+        let (prefix, name) = split_name(name)?;
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], 0o644)?;
+        write_octal(&mut header[108..116], 0)?;
+        write_octal(&mut header[116..124], 0)?;
+        write_octal(&mut header[124..136], data.len() as u64)?;
+        write_octal(&mut header[136..148], mtime)?;
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        // the checksum is computed with its own field filled with spaces
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)?;
+        let padding = (BLOCK - data.len() % BLOCK) % BLOCK;
+        self.writer.write_all(&[0u8; BLOCK][..padding])
+    }
+
+    // write the two zero blocks which end an archive, handing back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[0u8; 2 * BLOCK])?;
+        Ok(self.writer)
+    }
+}
+
+// helper function to split a path into the ustar (prefix, name) fields
+fn split_name(path: &str) -> io::Result<(&str, &str)> {
+    if path.len() <= 100 {
+        return Ok(("", path));
+    }
+
+    path.match_indices('/')
+        .map(|(at, _)| (&path[..at], &path[at + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && !name.is_empty() && name.len() <= 100)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path too long for tar"))
+}
+
+// helper function to fill a numeric header field with zero-padded octal and a NUL terminator
+fn write_octal(field: &mut [u8], value: u64) -> io::Result<()> {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    if digits.len() >= field.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "value too large for tar header",
+        ));
+    }
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+    Ok(())
+}
+
+// helper function to list the (path, contents) of the regular files in an archive
+#[cfg(test)]
+pub(crate) fn read_tar(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let field = |bytes: &[u8]| {
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8(bytes[..end].to_vec()).unwrap()
+    };
+
+    let mut files = Vec::new();
+    let mut offset = 0;
+    while offset + BLOCK <= archive.len() && archive[offset..offset + BLOCK] != [0u8; BLOCK] {
+        let header = &archive[offset..offset + BLOCK];
+        let (prefix, name) = (field(&header[345..500]), field(&header[..100]));
+        let size = u64::from_str_radix(&field(&header[124..136]), 8).unwrap() as usize;
+        let path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+
+        let start = offset + BLOCK;
+        files.push((path, archive[start..start + size].to_vec()));
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_files_including_long_names() {
+        let long_name = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        let mut tar = TarWriter::new(Vec::new());
+        tar.append("a.txt", b"alpha", 0).unwrap();
+        tar.append(&long_name, &[7u8; 600], 1_700_000_000).unwrap();
+        let archive = tar.finish().unwrap();
+
+        assert_eq!(archive.len() % BLOCK, 0);
+        assert_eq!(
+            read_tar(&archive),
+            vec![
+                ("a.txt".to_string(), b"alpha".to_vec()),
+                (long_name, vec![7u8; 600]),
+            ]
+        );
+
+        let mut tar = TarWriter::new(Vec::new());
+        assert!(tar.append(&"x".repeat(101), b"", 0).is_err());
+    }
+}
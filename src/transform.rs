@@ -0,0 +1,13 @@
+// src/transform.rs
+
+// dependencies
+use std::path::Path;
+
+// type alias for a user supplied body transform (e.g. minification), applied to the identity
+// body after it is read; it receives the resolved path, the MIME type and the bytes, and
+// returns the bytes to serve
+pub type TransformHook = Box<dyn Fn(&Path, &str, Vec<u8>) -> Vec<u8> + Send + Sync>;
+
+// type alias for a user supplied source of `<meta property content>` pairs (e.g. OpenGraph
+// tags for link previews), called with the resolved path of every HTML body served
+pub type HtmlMetaInjector = Box<dyn Fn(&Path) -> Vec<(String, String)> + Send + Sync>;
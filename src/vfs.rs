@@ -0,0 +1,161 @@
+// src/vfs.rs
+
+// dependencies
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// trait for the filesystem operations the static file server performs; the server only
+// touches the disk through this, so tests can count or intercept calls
+pub trait Vfs: Send + Sync {
+    // metadata for a path, following symlinks
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    // metadata for a path, without following a final symlink
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    // resolve a path to its absolute form, following every symlink on the way
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    // open a file for reading; with `nofollow` a symlink as the final component is refused
+    // (unix only)
+    fn open(&self, path: &Path, nofollow: bool) -> io::Result<File>;
+
+    // read a whole file into memory
+    fn read(&self, path: &Path, nofollow: bool) -> io::Result<Vec<u8>> {
+        let mut body = Vec::new();
+        io::Read::read_to_end(&mut self.open(path, nofollow)?, &mut body)?;
+        Ok(body)
+    }
+
+    // the paths of a directory's entries, in no particular order
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+}
+
+// struct type which represents the real filesystem
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdFs;
+
+// implement the Vfs trait for the StdFs type
+impl Vfs for StdFs {
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        std::fs::metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        std::fs::symlink_metadata(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+
+    fn open(&self, path: &Path, nofollow: bool) -> io::Result<File> {
+        let mut options = std::fs::OpenOptions::new();
+        options.read(true);
+
+        #[cfg(unix)]
+        if nofollow {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.custom_flags(libc::O_NOFOLLOW);
+        }
+        #[cfg(not(unix))]
+        let _ = nofollow;
+
+        options.open(path)
+    }
+}
+
+// boxed future returned by AsyncVfs reads
+#[cfg(feature = "tokio")]
+pub type VfsFuture<'a, T> = std::pin::Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+// trait for reading file bodies asynchronously; resolution still goes through Vfs, only the
+// body read (the part that can hang on a network mount) is asynchronous
+#[cfg(feature = "tokio")]
+pub trait AsyncVfs: Send + Sync {
+    // read a whole file into memory; with `nofollow` a final symlink is refused (unix only)
+    fn read<'a>(&'a self, path: &'a Path, nofollow: bool) -> VfsFuture<'a, Vec<u8>>;
+}
+
+// struct type which represents the real filesystem, read through tokio
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioFs;
+
+// implement the AsyncVfs trait for the TokioFs type
+#[cfg(feature = "tokio")]
+impl AsyncVfs for TokioFs {
+    fn read<'a>(&'a self, path: &'a Path, nofollow: bool) -> VfsFuture<'a, Vec<u8>> {
+        let path = path.to_path_buf();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || StdFs.read(&path, nofollow))
+                .await
+                .map_err(io::Error::other)?
+        })
+    }
+}
+
+// test double which forwards to the real filesystem while counting calls per operation
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    pub struct CountingFs {
+        calls: Mutex<Vec<(&'static str, PathBuf)>>,
+    }
+
+    impl CountingFs {
+        // how many times an operation was called for a path ending with the given suffix
+        pub fn count_for(&self, operation: &str, suffix: &str) -> usize {
+            self.calls
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(op, path)| *op == operation && path.ends_with(suffix))
+                .count()
+        }
+
+        fn record(&self, operation: &'static str, path: &Path) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push((operation, path.to_path_buf()));
+        }
+    }
+
+    impl Vfs for CountingFs {
+        fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+            self.record("metadata", path);
+            StdFs.metadata(path)
+        }
+
+        fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+            self.record("symlink_metadata", path);
+            StdFs.symlink_metadata(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            self.record("canonicalize", path);
+            StdFs.canonicalize(path)
+        }
+
+        fn open(&self, path: &Path, nofollow: bool) -> io::Result<File> {
+            self.record("open", path);
+            StdFs.open(path, nofollow)
+        }
+
+        fn read(&self, path: &Path, nofollow: bool) -> io::Result<Vec<u8>> {
+            self.record("read", path);
+            let mut body = Vec::new();
+            io::Read::read_to_end(&mut StdFs.open(path, nofollow)?, &mut body)?;
+            Ok(body)
+        }
+    }
+}
@@ -21,6 +21,7 @@ fn serves_js_and_css_mime_types() {
         mount_path: "/static".into(),
         root_dir: dir.path().to_path_buf(),
         serve_index: false,
+        ..Default::default()
     };
 
     let server = StaticServer::from_config(config);
@@ -48,6 +49,7 @@ fn blocks_path_traversal_attempts() {
         mount_path: "/static".into(),
         root_dir: dir.path().to_path_buf(),
         serve_index: false,
+        ..Default::default()
     };
 
     let server = StaticServer::from_config(config);
@@ -63,3 +65,33 @@ fn blocks_path_traversal_attempts() {
     std::fs::remove_file(&outside_file).unwrap();
 }
 
+#[test]
+fn serves_any_listed_index_file() {
+    let dir = tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("legacy")).unwrap();
+    std::fs::write(dir.path().join("legacy/index.htm"), "legacy home").unwrap();
+
+    // the default list only names index.html
+    let default = StaticServer::from_config(StaticServerConfig {
+        root_dir: dir.path().to_path_buf(),
+        serve_index: true,
+        ..Default::default()
+    });
+    assert!(matches!(
+        default.read_file("/legacy/"),
+        Err(ServeError::NotFound)
+    ));
+
+    let server = StaticServer::from_config(StaticServerConfig {
+        root_dir: dir.path().to_path_buf(),
+        serve_index: true,
+        index_files: vec![
+            "index.html".to_string(),
+            "index.htm".to_string(),
+            "default.html".to_string(),
+        ],
+        ..Default::default()
+    });
+    assert_eq!(server.read_file("/legacy/").unwrap().body, b"legacy home");
+}
+